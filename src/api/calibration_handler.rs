@@ -0,0 +1,108 @@
+use crate::config::app_config::ConfigStore;
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::calibration::{CalibrationMark, CalibrationSession};
+use crate::managers::servo_manager::ServoManager;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct PulseRequest {
+    pulse_width: u16,
+}
+
+#[derive(Deserialize)]
+pub struct NudgeRequest {
+    delta: i32, // Ticks, negative to move down
+}
+
+#[derive(Deserialize)]
+pub struct MarkRequest {
+    mark: CalibrationMark,
+}
+
+#[derive(Deserialize)]
+pub struct CommitQuery {
+    #[serde(default)]
+    persist: bool, // Also write the change back to the config file
+    #[serde(default)]
+    swap_ends: bool, // Accept a min mark above the max mark as a mirrored mount
+}
+
+pub async fn start_calibration(
+    manager: web::Data<ServoManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    session_response(manager.start_calibration(&path).await)
+}
+
+pub async fn get_calibration(
+    manager: web::Data<ServoManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    session_response(manager.get_calibration(&path).await)
+}
+
+pub async fn set_pulse(
+    manager: web::Data<ServoManager>,
+    path: web::Path<String>,
+    req: web::Json<PulseRequest>,
+) -> impl Responder {
+    session_response(manager.set_calibration_pulse(&path, req.pulse_width).await)
+}
+
+pub async fn nudge(
+    manager: web::Data<ServoManager>,
+    path: web::Path<String>,
+    req: web::Json<NudgeRequest>,
+) -> impl Responder {
+    session_response(manager.nudge_calibration(&path, req.delta).await)
+}
+
+pub async fn mark(
+    manager: web::Data<ServoManager>,
+    path: web::Path<String>,
+    req: web::Json<MarkRequest>,
+) -> impl Responder {
+    session_response(manager.mark_calibration(&path, req.mark).await)
+}
+
+pub async fn commit_calibration(
+    manager: web::Data<ServoManager>,
+    config_store: web::Data<ConfigStore>,
+    path: web::Path<String>,
+    query: web::Query<CommitQuery>,
+) -> impl Responder {
+    let config = match manager.commit_calibration(&path, query.swap_ends).await {
+        Ok(config) => config,
+        Err(HardwareError::NotFound(message)) => return HttpResponse::NotFound().body(message),
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+
+    if query.persist {
+        if let Err(e) = config_store.persist(&manager).await {
+            log::error!("Failed to save configuration: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Calibration applied but not saved: {}", e));
+        }
+    }
+
+    HttpResponse::Ok().json(config)
+}
+
+pub async fn cancel_calibration(
+    manager: web::Data<ServoManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match manager.cancel_calibration(&path).await {
+        Ok(_) => HttpResponse::Ok().json("Calibration cancelled"),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+fn session_response(result: Result<CalibrationSession, HardwareError>) -> HttpResponse {
+    match result {
+        Ok(session) => HttpResponse::Ok().json(session),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
@@ -1,16 +1,92 @@
-use crate::managers::servo_manager::ServoManager;
-use crate::AudioManager;
+use crate::hardware::servo::config::ServoTarget;
+use crate::hardware::servo::Easing;
 use serde::Deserialize;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Command {
     MoveServo {
         servo_name: String,
-        position: f64,
+        position: ServoTarget, // Degrees, or one of the servo's named positions
+        #[serde(default)]
         duration: u64,
+        #[serde(default)]
+        easing: Easing,
+    },
+    SetSpeed {
+        servo_name: String,
+        speed: f64, // -1.0..=1.0, continuous servos only
+        #[serde(default)]
+        duration: Option<u64>, // Back to neutral after this many ms
+    },
+    MoveServos {
+        positions: HashMap<String, ServoTarget>, // Written in lock-step
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        easing: Easing,
+    },
+    ToggleServo {
+        servo_name: String,
+        #[serde(default)]
+        positions: Option<[String; 2]>, // Defaults to the servo's two named positions
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        easing: Easing,
+    },
+    MoveGroup {
+        group_name: String,
+        position: ServoTarget,
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        easing: Easing,
+        #[serde(default)]
+        overrides: HashMap<String, ServoTarget>,
+        #[serde(default)]
+        stagger_ms: Option<u64>,
+    },
+    Home {
+        #[serde(default)]
+        servo_name: Option<String>, // Every servo with a home_angle when omitted
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        easing: Easing,
+    },
+    RecallPose {
+        pose_name: String,
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        easing: Easing,
+    },
+    SetLed {
+        led_name: String,
+        brightness: f64, // Percent
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        easing: Easing,
+    },
+    BlinkLed {
+        led_name: String,
+        on_ms: u64,
+        off_ms: u64,
+        #[serde(default)]
+        count: Option<u32>,
+        #[serde(default = "full_brightness")]
+        brightness: f64,
+    },
+    SetOutput {
+        output_name: String,
+        on: bool,
+    },
+    PulseOutput {
+        output_name: String,
+        duration: u64, // Out of its default state for this many ms, then back
     },
     PlayAudio {
         file: String,
@@ -20,33 +96,6 @@ pub enum Command {
     },
 }
 
-impl Command {
-    pub async fn execute(
-        &self,
-        servo_manager: Arc<ServoManager>,
-        audio_manager: Arc<AudioManager>,
-    ) {
-        match self {
-            Command::MoveServo {
-                servo_name,
-                position,
-                duration: _duration,
-            } => {
-                // Call the move_servo function on ServoManager
-                if let Err(e) = servo_manager.move_servo(&servo_name, *position).await {
-                    eprintln!("Error moving servo: {:?}", e);
-                }
-            }
-            Command::PlayAudio { file } => {
-                // Call the play_audio function on AudioManager
-                if let Err(e) = audio_manager.play_audio(&file.clone()).await {
-                    eprintln!("Error playing audio: {:?}", e);
-                }
-            }
-            Command::Pause { duration } => {
-                // Pause for the specified _duration
-                tokio::time::sleep(Duration::from_millis(*duration)).await;
-            }
-        }
-    }
+fn full_brightness() -> f64 {
+    100.0
 }
@@ -0,0 +1,43 @@
+use crate::hardware::servo::state::EstopMode;
+use crate::managers::routine_manager::RoutineManager;
+use crate::managers::servo_manager::ServoManager;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct EstopRequest {
+    #[serde(default)]
+    mode: EstopMode,
+}
+
+// The body is optional so a bare POST always stops, relaxing every servo
+pub async fn engage(
+    routine_manager: web::Data<RoutineManager>,
+    req: Option<web::Json<EstopRequest>>,
+) -> impl Responder {
+    let mode = req.map(|req| req.mode).unwrap_or_default();
+
+    match routine_manager.emergency_stop(mode).await {
+        Ok(cancelled) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Emergency stop engaged",
+            "mode": mode,
+            "cancelled_tasks": cancelled
+        })),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn status(manager: web::Data<ServoManager>) -> impl Responder {
+    let estop = manager.estop_status().await;
+    HttpResponse::Ok().json(serde_json::json!({
+        "engaged": estop.is_some(),
+        "estop": estop
+    }))
+}
+
+pub async fn clear(manager: web::Data<ServoManager>) -> impl Responder {
+    match manager.clear_estop().await {
+        Ok(()) => HttpResponse::Ok().json("Emergency stop cleared"),
+        Err(e) => HttpResponse::Conflict().body(e.to_string()),
+    }
+}
@@ -1,38 +1,412 @@
 use crate::api::command::Command;
+use crate::config::app_config::ConfigStore;
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::config::{Pca9685Config, ServoConfig, ServoTarget};
+use crate::hardware::servo::lease::ConflictPolicy;
+use crate::hardware::servo::Easing;
 use crate::managers::routine_manager::RoutineManager;
-use crate::managers::servo_manager::ServoManager;
+use crate::managers::servo_manager::{GroupMove, ServoManager};
 use actix_web::{web, HttpResponse, Responder};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[derive(Deserialize)]
 pub struct MoveServoRequest {
-    pub angle: f64,
+    #[serde(alias = "position")]
+    pub angle: ServoTarget, // Degrees, or one of the servo's named positions
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Deserialize)]
+pub struct HomeRequest {
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Deserialize)]
+pub struct SpeedRequest {
+    pub speed: f64, // -1.0..=1.0, 0 stops
+    #[serde(default)]
+    pub duration: Option<u64>, // Back to neutral after this many ms
+}
+
+#[derive(Deserialize)]
+pub struct MoveServosRequest {
+    pub positions: HashMap<String, ServoTarget>, // Servo name to angle or position name
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Deserialize)]
+pub struct ToggleRequest {
+    #[serde(default)]
+    pub positions: Option<[String; 2]>, // Defaults to the servo's two named positions
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Deserialize)]
+pub struct MoveGroupRequest {
+    #[serde(alias = "position")]
+    pub angle: ServoTarget,
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub easing: Easing,
+    #[serde(default)]
+    pub overrides: HashMap<String, ServoTarget>,
+    #[serde(default)]
+    pub stagger_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct PersistQuery {
+    #[serde(default)]
+    pub persist: bool, // Also write the change back to the config file
 }
 
 pub async fn list_controllers(manager: web::Data<ServoManager>) -> impl Responder {
-    let controllers = manager.list_controllers().await;
+    let controllers = manager.controller_statuses().await;
     HttpResponse::Ok().json(controllers)
 }
 
+// Everything a simulated controller has been sent, for checking routines without hardware
+pub async fn simulated_controller(
+    controller_id: web::Path<String>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    match manager.simulated_board(&controller_id).await {
+        Ok(board) => HttpResponse::Ok().json(serde_json::json!({
+            "channels": board.channels(),
+            "writes": board.writes()
+        })),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn create_controller(
+    req: web::Json<Pca9685Config>,
+    query: web::Query<PersistQuery>,
+    manager: web::Data<ServoManager>,
+    config_store: web::Data<ConfigStore>,
+) -> impl Responder {
+    let result = manager.add_controller(req.into_inner()).await;
+    config_change_response(
+        result,
+        &query,
+        &manager,
+        &config_store,
+        "Controller created",
+    )
+    .await
+}
+
+pub async fn update_controller(
+    controller_id: web::Path<String>,
+    req: web::Json<Pca9685Config>,
+    query: web::Query<PersistQuery>,
+    manager: web::Data<ServoManager>,
+    config_store: web::Data<ConfigStore>,
+) -> impl Responder {
+    let result = manager
+        .update_controller(&controller_id, req.into_inner())
+        .await;
+    config_change_response(
+        result,
+        &query,
+        &manager,
+        &config_store,
+        "Controller updated",
+    )
+    .await
+}
+
+pub async fn delete_controller(
+    controller_id: web::Path<String>,
+    query: web::Query<PersistQuery>,
+    manager: web::Data<ServoManager>,
+    config_store: web::Data<ConfigStore>,
+) -> impl Responder {
+    let result = manager.remove_controller(&controller_id).await;
+    config_change_response(
+        result,
+        &query,
+        &manager,
+        &config_store,
+        "Controller deleted",
+    )
+    .await
+}
+
 pub async fn list_servos(manager: web::Data<ServoManager>) -> impl Responder {
     let servos = manager.list_servos().await;
     HttpResponse::Ok().json(servos)
 }
 
+pub async fn get_servo(
+    servo_name: web::Path<String>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    match manager.get_servo(&servo_name).await {
+        Ok(servo) => HttpResponse::Ok().json(servo),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn create_servo(
+    req: web::Json<ServoConfig>,
+    query: web::Query<PersistQuery>,
+    manager: web::Data<ServoManager>,
+    config_store: web::Data<ConfigStore>,
+) -> impl Responder {
+    let result = manager.create_servo(req.into_inner()).await;
+    config_change_response(result, &query, &manager, &config_store, "Servo created").await
+}
+
+pub async fn update_servo(
+    servo_name: web::Path<String>,
+    req: web::Json<ServoConfig>,
+    query: web::Query<PersistQuery>,
+    manager: web::Data<ServoManager>,
+    config_store: web::Data<ConfigStore>,
+) -> impl Responder {
+    let result = manager.update_servo(&servo_name, req.into_inner()).await;
+    config_change_response(result, &query, &manager, &config_store, "Servo updated").await
+}
+
+pub async fn delete_servo(
+    servo_name: web::Path<String>,
+    query: web::Query<PersistQuery>,
+    manager: web::Data<ServoManager>,
+    config_store: web::Data<ConfigStore>,
+) -> impl Responder {
+    let result = manager.remove_servo(&servo_name).await;
+    config_change_response(result, &query, &manager, &config_store, "Servo deleted").await
+}
+
+async fn config_change_response(
+    result: Result<(), HardwareError>,
+    query: &PersistQuery,
+    manager: &ServoManager,
+    config_store: &ConfigStore,
+    message: &str,
+) -> HttpResponse {
+    match result {
+        Ok(_) => {}
+        Err(HardwareError::NotFound(message)) => return HttpResponse::NotFound().body(message),
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    }
+
+    if query.persist {
+        if let Err(e) = config_store.persist(manager).await {
+            log::error!("Failed to save configuration: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Change applied but not saved: {}", e));
+        }
+    }
+
+    HttpResponse::Ok().json(message)
+}
+
 pub async fn move_servo(
     servo_name: web::Path<String>,
     req: web::Json<MoveServoRequest>,
     manager: web::Data<ServoManager>,
 ) -> impl Responder {
-    match manager.move_servo(&servo_name, req.angle).await {
-        Ok(_) => HttpResponse::Ok().json("Servo moved successfully"),
+    match manager
+        .move_servo_to(&servo_name, &req.angle, req.duration, req.easing)
+        .await
+    {
+        Ok(outcome) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Servo moved successfully",
+            "outcome": outcome
+        })),
         Err(e) => HttpResponse::BadRequest().body(e.to_string()),
     }
 }
+
+pub async fn toggle_servo(
+    servo_name: web::Path<String>,
+    req: web::Json<ToggleRequest>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    let req = req.into_inner();
+    match manager
+        .toggle_servo(&servo_name, req.positions, req.duration, req.easing)
+        .await
+    {
+        Ok((position, outcome)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("Servo toggled to '{}'", position),
+            "position": position,
+            "outcome": outcome
+        })),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn set_speed(
+    servo_name: web::Path<String>,
+    req: web::Json<SpeedRequest>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    match manager
+        .set_speed(&servo_name, req.speed, req.duration)
+        .await
+    {
+        Ok(()) => HttpResponse::Ok().json("Servo speed set"),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn move_servos(
+    req: web::Json<MoveServosRequest>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    let req = req.into_inner();
+    let targets = match manager
+        .resolve_targets(req.positions.into_iter().collect())
+        .await
+    {
+        Ok(targets) => targets,
+        Err(HardwareError::NotFound(message)) => return HttpResponse::NotFound().body(message),
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+
+    match manager
+        .move_servos_synced(targets, req.duration, req.easing)
+        .await
+    {
+        Ok(outcomes) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Servos moved successfully",
+            "servos": outcomes
+        })),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn home_servo(
+    servo_name: web::Path<String>,
+    req: web::Json<HomeRequest>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    match manager
+        .home_servo(&servo_name, req.duration, req.easing)
+        .await
+    {
+        Ok(outcome) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Servo homed",
+            "outcome": outcome
+        })),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn home_all(
+    req: web::Json<HomeRequest>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    match manager.home_all(req.duration, req.easing).await {
+        Ok(outcomes) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "All servos homed",
+            "servos": outcomes
+        })),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn list_groups(manager: web::Data<ServoManager>) -> impl Responder {
+    let groups = manager.list_groups().await;
+    HttpResponse::Ok().json(groups)
+}
+
+pub async fn list_interlocks(manager: web::Data<ServoManager>) -> impl Responder {
+    let interlocks = manager.list_interlocks().await;
+    HttpResponse::Ok().json(interlocks)
+}
+
+pub async fn move_group(
+    group_name: web::Path<String>,
+    req: web::Json<MoveGroupRequest>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    let req = req.into_inner();
+    let group_move = GroupMove {
+        target: req.angle,
+        duration_ms: req.duration,
+        easing: req.easing,
+        overrides: req.overrides,
+        stagger_ms: req.stagger_ms,
+    };
+
+    match manager.move_group(&group_name, group_move).await {
+        Ok(outcomes) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Group moved successfully",
+            "servos": outcomes
+        })),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn relax_servo(
+    servo_name: web::Path<String>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    relax_response(manager.relax_servo(&servo_name).await, "Servo relaxed")
+}
+
+pub async fn relax_group(
+    group_name: web::Path<String>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    relax_response(manager.relax_group(&group_name).await, "Group relaxed")
+}
+
+pub async fn relax_controller(
+    controller_id: web::Path<String>,
+    manager: web::Data<ServoManager>,
+) -> impl Responder {
+    relax_response(
+        manager.relax_controller(&controller_id).await,
+        "Controller servos relaxed",
+    )
+}
+
+pub async fn relax_all(manager: web::Data<ServoManager>) -> impl Responder {
+    relax_response(manager.relax_all().await, "All servos relaxed")
+}
+
+fn relax_response(result: Result<(), HardwareError>, message: &str) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponse::Ok().json(message),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e @ HardwareError::Busy(_)) => HttpResponse::Conflict().body(e.to_string()),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct RoutineRequest {
     pub commands: Vec<Command>,
+    #[serde(default)]
+    pub priority: i32, // Used when the routine's servos are already leased
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
 }
 
 pub struct RoutineHandler {
@@ -45,14 +419,21 @@ impl RoutineHandler {
     }
 
     pub async fn execute_routine(&self, req: web::Json<RoutineRequest>) -> impl Responder {
-        let commands = req.into_inner().commands;
+        let req = req.into_inner();
         let routine_manager = Arc::clone(&self.routine_manager);
 
         // Execute routine asynchronously through the RoutineManager
-        tokio::spawn(async move {
-            routine_manager.execute_routine(commands).await;
-        });
-
-        HttpResponse::Ok().json("Routine started")
+        match routine_manager
+            .start_routine(req.commands, req.priority, req.on_conflict)
+            .await
+        {
+            Ok(owner) => HttpResponse::Ok().json(serde_json::json!({
+                "message": "Routine started",
+                "owner": owner
+            })),
+            Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+            Err(HardwareError::Timeout(message)) => HttpResponse::RequestTimeout().body(message),
+            Err(e) => HttpResponse::Conflict().body(e.to_string()),
+        }
     }
 }
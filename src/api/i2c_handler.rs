@@ -0,0 +1,16 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::managers::servo_manager::ServoManager;
+use actix_web::{web, HttpResponse, Responder};
+
+pub async fn scan_bus(manager: web::Data<ServoManager>, bus: web::Path<String>) -> impl Responder {
+    // Only bus numbers, so the path can't point the scan at an arbitrary file
+    if bus.is_empty() || !bus.chars().all(|c| c.is_ascii_digit()) {
+        return HttpResponse::BadRequest().body(format!("Invalid I2C bus number: {}", bus));
+    }
+
+    match manager.scan_i2c_bus(&bus).await {
+        Ok(scan) => HttpResponse::Ok().json(scan),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,62 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::lease::{LeaseRequest, Owner, ROUTINE_OWNER_PREFIX};
+use crate::managers::servo_manager::ServoManager;
+use actix_web::dev::ServiceRequest;
+use actix_web::{web, HttpResponse, Responder};
+use std::sync::Arc;
+
+// Requests carrying this header may move the servos leased to the named owner
+const OWNER_HEADER: &str = "X-Servo-Owner";
+
+pub fn request_owner(req: &ServiceRequest) -> Result<Option<Arc<Owner>>, HardwareError> {
+    let owner = match req.headers().get(OWNER_HEADER) {
+        Some(owner) => owner.to_str().map_err(|_| {
+            HardwareError::InvalidParameter(format!("{} must be plain text", OWNER_HEADER))
+        })?,
+        None => return Ok(None),
+    };
+    check_owner(owner)?;
+    Ok(Some(Arc::new(Owner::new(owner))))
+}
+
+// Routine owners only exist while their routine runs, so a client can't act as one
+fn check_owner(owner: &str) -> Result<(), HardwareError> {
+    if owner.starts_with(ROUTINE_OWNER_PREFIX) {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Owner names starting with '{}' are reserved for routines",
+            ROUTINE_OWNER_PREFIX
+        )));
+    }
+    Ok(())
+}
+
+pub async fn list_leases(manager: web::Data<ServoManager>) -> impl Responder {
+    HttpResponse::Ok().json(manager.list_leases().await)
+}
+
+pub async fn acquire_lease(
+    manager: web::Data<ServoManager>,
+    req: web::Json<LeaseRequest>,
+) -> impl Responder {
+    let req = req.into_inner();
+    if let Err(e) = check_owner(&req.owner) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    let owner = req.owner.clone();
+    match manager.acquire_lease(req).await {
+        Ok(()) => HttpResponse::Ok().json(format!("Servos leased to '{}'", owner)),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(HardwareError::Timeout(message)) => HttpResponse::RequestTimeout().body(message),
+        Err(e) => HttpResponse::Conflict().body(e.to_string()),
+    }
+}
+
+pub async fn release_lease(
+    manager: web::Data<ServoManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match manager.release_lease(&path).await {
+        Ok(()) => HttpResponse::Ok().json(format!("Servos released by '{}'", path)),
+        Err(e) => HttpResponse::NotFound().body(e.to_string()),
+    }
+}
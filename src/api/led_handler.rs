@@ -0,0 +1,61 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::led::BlinkPattern;
+use crate::hardware::servo::Easing;
+use crate::managers::led_manager::LedManager;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct BrightnessRequest {
+    brightness: f64, // Percent
+    #[serde(default)]
+    duration: u64,
+    #[serde(default)]
+    easing: Easing,
+}
+
+pub async fn list_leds(led_manager: web::Data<LedManager>) -> impl Responder {
+    HttpResponse::Ok().json(led_manager.list_leds().await)
+}
+
+pub async fn get_led(
+    led_manager: web::Data<LedManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match led_manager.get_led(&path).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::NotFound().body(e.to_string()),
+    }
+}
+
+pub async fn set_brightness(
+    led_manager: web::Data<LedManager>,
+    path: web::Path<String>,
+    req: web::Json<BrightnessRequest>,
+) -> impl Responder {
+    match led_manager
+        .set_brightness(&path, req.brightness, req.duration, req.easing)
+        .await
+    {
+        Ok(_) => HttpResponse::Ok().json(json!({
+            "message": format!("LED '{}' set to {}%", path, req.brightness)
+        })),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn blink(
+    led_manager: web::Data<LedManager>,
+    path: web::Path<String>,
+    pattern: web::Json<BlinkPattern>,
+) -> impl Responder {
+    match led_manager.blink(&path, pattern.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().json(json!({
+            "message": format!("LED '{}' blinking", path)
+        })),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
@@ -1,4 +1,12 @@
 mod audio_handler;
+mod calibration_handler;
 pub mod command;
+mod estop_handler;
 pub(crate) mod handlers;
+mod i2c_handler;
+mod lease_handler;
+mod led_handler;
+mod output_handler;
+mod pose_handler;
 pub(crate) mod routes;
+mod trace_handler;
@@ -0,0 +1,68 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::managers::output_manager::OutputManager;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct PulseRequest {
+    duration: u64, // Out of its default state for this many ms, then back
+}
+
+pub async fn list_outputs(output_manager: web::Data<OutputManager>) -> impl Responder {
+    HttpResponse::Ok().json(output_manager.list_outputs().await)
+}
+
+pub async fn get_output(
+    output_manager: web::Data<OutputManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match output_manager.get_output(&path).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::NotFound().body(e.to_string()),
+    }
+}
+
+pub async fn switch_on(
+    output_manager: web::Data<OutputManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    switch(&output_manager, &path, true).await
+}
+
+pub async fn switch_off(
+    output_manager: web::Data<OutputManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    switch(&output_manager, &path, false).await
+}
+
+pub async fn pulse(
+    output_manager: web::Data<OutputManager>,
+    path: web::Path<String>,
+    req: web::Json<PulseRequest>,
+) -> impl Responder {
+    match output_manager.pulse(&path, req.duration).await {
+        Ok(_) => HttpResponse::Ok().json(json!({
+            "message": format!("Output '{}' pulsed for {}ms", path, req.duration)
+        })),
+        Err(e) => output_error(e),
+    }
+}
+
+async fn switch(output_manager: &OutputManager, name: &str, on: bool) -> HttpResponse {
+    match output_manager.set_output(name, on).await {
+        Ok(_) => HttpResponse::Ok().json(json!({
+            "message": format!("Output '{}' switched {}", name, if on { "on" } else { "off" })
+        })),
+        Err(e) => output_error(e),
+    }
+}
+
+fn output_error(e: HardwareError) -> HttpResponse {
+    match e {
+        HardwareError::NotFound(message) => HttpResponse::NotFound().body(message),
+        HardwareError::InvalidState(message) => HttpResponse::Conflict().body(message),
+        e => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
@@ -0,0 +1,80 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::Easing;
+use crate::managers::pose_manager::PoseManager;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct SavePoseRequest {
+    name: String,
+    #[serde(default)]
+    servos: Option<Vec<String>>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RecallPoseRequest {
+    #[serde(default)]
+    duration: u64,
+    #[serde(default)]
+    easing: Easing,
+}
+
+pub async fn list_poses(pose_manager: web::Data<PoseManager>) -> impl Responder {
+    HttpResponse::Ok().json(pose_manager.list_poses().await)
+}
+
+pub async fn get_pose(
+    pose_manager: web::Data<PoseManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match pose_manager.get_pose(&path).await {
+        Ok(pose) => HttpResponse::Ok().json(pose),
+        Err(e) => HttpResponse::NotFound().body(e.to_string()),
+    }
+}
+
+pub async fn save_pose(
+    pose_manager: web::Data<PoseManager>,
+    req: web::Json<SavePoseRequest>,
+) -> impl Responder {
+    let req = req.into_inner();
+    match pose_manager
+        .save_pose(&req.name, req.servos, req.description)
+        .await
+    {
+        Ok(pose) => HttpResponse::Ok().json(pose),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+pub async fn delete_pose(
+    pose_manager: web::Data<PoseManager>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match pose_manager.delete_pose(&path).await {
+        Ok(_) => HttpResponse::Ok().json("Pose deleted"),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn recall_pose(
+    pose_manager: web::Data<PoseManager>,
+    path: web::Path<String>,
+    req: web::Json<RecallPoseRequest>,
+) -> impl Responder {
+    match pose_manager
+        .recall_pose(&path, req.duration, req.easing)
+        .await
+    {
+        Ok(outcomes) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Pose recalled",
+            "servos": outcomes
+        })),
+        Err(HardwareError::NotFound(message)) => HttpResponse::NotFound().body(message),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
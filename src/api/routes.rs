@@ -1,16 +1,90 @@
 use crate::api::audio_handler;
 use crate::api::audio_handler::get_duration;
+use crate::api::calibration_handler;
+use crate::api::estop_handler;
 use crate::api::handlers::{
-    list_controllers, list_servos, move_servo, RoutineHandler, RoutineRequest,
+    create_controller, create_servo, delete_controller, delete_servo, get_servo, home_all,
+    home_servo, list_controllers, list_groups, list_interlocks, list_servos, move_group,
+    move_servo, move_servos, relax_all, relax_controller, relax_group, relax_servo, set_speed,
+    simulated_controller, toggle_servo, update_controller, update_servo, RoutineHandler,
+    RoutineRequest,
 };
-use actix_web::web;
+use crate::api::i2c_handler;
+use crate::api::lease_handler;
+use crate::api::led_handler;
+use crate::api::output_handler;
+use crate::api::pose_handler;
+use crate::api::trace_handler;
+use crate::hardware::servo::lease::run_as;
+use actix_web::dev::Service;
+use actix_web::{error, web};
 
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
+            // Handlers move servos on behalf of the owner named in the request, if any
+            .wrap_fn(|req, srv| {
+                let owner = lease_handler::request_owner(&req);
+                let call = owner.map(|owner| run_as(owner, srv.call(req)));
+                async move {
+                    match call {
+                        Ok(call) => call.await,
+                        Err(e) => Err(error::ErrorBadRequest(e.to_string())),
+                    }
+                }
+            })
+            .route("/estop", web::post().to(estop_handler::engage))
+            .route("/estop", web::get().to(estop_handler::status))
+            .route("/estop", web::delete().to(estop_handler::clear))
             .route("/controllers", web::get().to(list_controllers))
+            .route("/controllers", web::post().to(create_controller))
+            .route("/controllers/{id}", web::put().to(update_controller))
+            .route("/controllers/{id}", web::delete().to(delete_controller))
+            .route("/controllers/{id}/relax", web::post().to(relax_controller))
+            .route(
+                "/controllers/{id}/simulated",
+                web::get().to(simulated_controller),
+            )
             .route("/servos", web::get().to(list_servos))
+            .route("/servos", web::post().to(create_servo))
+            .route("/servos/home", web::post().to(home_all))
+            .route("/servos/move", web::post().to(move_servos))
+            .route("/servos/{name}", web::get().to(get_servo))
+            .route("/servos/{name}", web::put().to(update_servo))
+            .route("/servos/{name}", web::delete().to(delete_servo))
             .route("/servos/{name}/move", web::post().to(move_servo))
+            .route("/servos/{name}/toggle", web::post().to(toggle_servo))
+            .route("/servos/{name}/home", web::post().to(home_servo))
+            .route("/servos/{name}/relax", web::post().to(relax_servo))
+            .route("/servos/{name}/speed", web::post().to(set_speed))
+            .service(
+                web::scope("/servos/{name}/calibration")
+                    .route("", web::post().to(calibration_handler::start_calibration))
+                    .route("", web::get().to(calibration_handler::get_calibration))
+                    .route(
+                        "",
+                        web::delete().to(calibration_handler::cancel_calibration),
+                    )
+                    .route("/pulse", web::post().to(calibration_handler::set_pulse))
+                    .route("/nudge", web::post().to(calibration_handler::nudge))
+                    .route("/mark", web::post().to(calibration_handler::mark))
+                    .route(
+                        "/commit",
+                        web::post().to(calibration_handler::commit_calibration),
+                    ),
+            )
+            .route("/i2c/{bus}/scan", web::get().to(i2c_handler::scan_bus))
+            .route("/leases", web::get().to(lease_handler::list_leases))
+            .route("/leases", web::post().to(lease_handler::acquire_lease))
+            .route(
+                "/leases/{owner}",
+                web::delete().to(lease_handler::release_lease),
+            )
+            .route("/groups", web::get().to(list_groups))
+            .route("/groups/{name}/move", web::post().to(move_group))
+            .route("/groups/{name}/relax", web::post().to(relax_group))
+            .route("/interlocks", web::get().to(list_interlocks))
+            .route("/relax", web::post().to(relax_all))
             .route(
                 "/routine",
                 web::post().to(
@@ -20,6 +94,40 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                     },
                 ),
             )
+            .service(
+                web::scope("/poses")
+                    .route("", web::get().to(pose_handler::list_poses))
+                    .route("", web::post().to(pose_handler::save_pose))
+                    .route("/{name}", web::get().to(pose_handler::get_pose))
+                    .route("/{name}", web::delete().to(pose_handler::delete_pose))
+                    .route("/{name}/recall", web::post().to(pose_handler::recall_pose)),
+            )
+            .service(
+                web::scope("/leds")
+                    .route("", web::get().to(led_handler::list_leds))
+                    .route("/{name}", web::get().to(led_handler::get_led))
+                    .route(
+                        "/{name}/brightness",
+                        web::post().to(led_handler::set_brightness),
+                    )
+                    .route("/{name}/blink", web::post().to(led_handler::blink)),
+            )
+            .service(
+                web::scope("/outputs")
+                    .route("", web::get().to(output_handler::list_outputs))
+                    .route("/{name}", web::get().to(output_handler::get_output))
+                    .route("/{name}/on", web::post().to(output_handler::switch_on))
+                    .route("/{name}/off", web::post().to(output_handler::switch_off))
+                    .route("/{name}/pulse", web::post().to(output_handler::pulse)),
+            )
+            .service(
+                web::scope("/trace")
+                    .route("", web::get().to(trace_handler::trace_status))
+                    .route("/start", web::post().to(trace_handler::start_trace))
+                    .route("/stop", web::post().to(trace_handler::stop_trace))
+                    .route("/golden", web::post().to(trace_handler::record_golden))
+                    .route("/verify", web::post().to(trace_handler::verify_golden)),
+            )
             .service(
                 web::scope("/audio")
                     .route("", web::get().to(audio_handler::list_audio_files))
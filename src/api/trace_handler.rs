@@ -0,0 +1,111 @@
+use crate::api::handlers::RoutineRequest;
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::trace::{compare_traces, TraceEntry, TraceTolerance};
+use crate::managers::routine_manager::RoutineManager;
+use crate::managers::servo_manager::ServoManager;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct StartTraceRequest {
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct GoldenTraceRequest {
+    pub path: String, // JSONL file in the trace directory the golden trace is saved to or read from
+    #[serde(flatten)]
+    pub routine: RoutineRequest,
+    #[serde(default)]
+    pub tolerance: TraceTolerance, // Only used when verifying
+}
+
+pub async fn trace_status(manager: web::Data<ServoManager>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "recording": manager.trace().file_status()
+    }))
+}
+
+pub async fn start_trace(
+    manager: web::Data<ServoManager>,
+    req: web::Json<StartTraceRequest>,
+) -> impl Responder {
+    match manager.trace().start_file(&req.path) {
+        Ok(()) => HttpResponse::Ok().json(format!("Recording I2C trace to '{}'", req.path)),
+        Err(HardwareError::InvalidState(message)) => HttpResponse::Conflict().body(message),
+        Err(HardwareError::InvalidParameter(message)) => HttpResponse::BadRequest().body(message),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn stop_trace(manager: web::Data<ServoManager>) -> impl Responder {
+    match manager.trace().stop_file() {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::Conflict().body(e.to_string()),
+    }
+}
+
+// Runs the routine and saves everything it wrote as the golden trace
+pub async fn record_golden(
+    servo_manager: web::Data<ServoManager>,
+    routine_manager: web::Data<RoutineManager>,
+    req: web::Json<GoldenTraceRequest>,
+) -> impl Responder {
+    let req = req.into_inner();
+    // Checked before the routine runs, not after
+    if let Err(e) = servo_manager.trace().resolve(&req.path) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    let entries = match capture_routine(&servo_manager, routine_manager, req.routine).await {
+        Ok(entries) => entries,
+        Err(response) => return response,
+    };
+
+    match servo_manager.trace().write_trace(&req.path, &entries) {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("Golden trace saved to '{}'", req.path),
+            "writes": entries.len()
+        })),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Runs the routine and compares what it wrote against the golden trace
+pub async fn verify_golden(
+    servo_manager: web::Data<ServoManager>,
+    routine_manager: web::Data<RoutineManager>,
+    req: web::Json<GoldenTraceRequest>,
+) -> impl Responder {
+    let req = req.into_inner();
+    let golden = match servo_manager.trace().read_trace(&req.path) {
+        Ok(golden) => golden,
+        Err(HardwareError::IoError(e)) => {
+            return HttpResponse::NotFound().body(format!("{}: {}", req.path, e))
+        }
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+
+    match capture_routine(&servo_manager, routine_manager, req.routine).await {
+        Ok(entries) => HttpResponse::Ok().json(compare_traces(&golden, &entries, req.tolerance)),
+        Err(response) => response,
+    }
+}
+
+async fn capture_routine(
+    servo_manager: &ServoManager,
+    routine_manager: web::Data<RoutineManager>,
+    routine: RoutineRequest,
+) -> Result<Vec<TraceEntry>, HttpResponse> {
+    let capture = servo_manager.trace().start_capture();
+    let result = routine_manager
+        .into_inner()
+        .run_routine(routine.commands, routine.priority, routine.on_conflict)
+        .await;
+    let entries = servo_manager.trace().finish_capture(capture);
+
+    match result {
+        Ok(_) => Ok(entries),
+        Err(HardwareError::NotFound(message)) => Err(HttpResponse::NotFound().body(message)),
+        Err(e) => Err(HttpResponse::Conflict().body(e.to_string())),
+    }
+}
@@ -0,0 +1,162 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::audio::config::AudioConfig;
+use crate::hardware::led::LedConfig;
+use crate::hardware::output::DigitalOutputConfig;
+use crate::hardware::servo::config::{
+    InterlockConfig, MotionConfig, Pca9685Config, PoseConfig, ServoConfig, ServoGroupConfig,
+    TraceConfig,
+};
+use crate::managers::servo_manager::ServoManager;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use tokio::sync::Mutex;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub audio: AudioConfig,
+    pub controllers: Vec<Pca9685Config>,
+    pub servos: Vec<ServoConfig>,
+    #[serde(default)]
+    pub groups: Vec<ServoGroupConfig>,
+    #[serde(default)]
+    pub interlocks: Vec<InterlockConfig>,
+    #[serde(default)]
+    pub motion: MotionConfig,
+    #[serde(default)]
+    pub trace: TraceConfig,
+    #[serde(default)]
+    pub poses: PoseConfig,
+    #[serde(default)]
+    pub leds: Vec<LedConfig>,
+    #[serde(default)]
+    pub digital_outputs: Vec<DigitalOutputConfig>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub log_level: String,
+}
+
+// The config file on disk, kept in step with runtime changes when asked to persist
+pub struct ConfigStore {
+    path: String,
+    config: Mutex<Config>,
+}
+
+impl ConfigStore {
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let config_data = fs::read_to_string(path)?;
+        let config = serde_json::from_str(&config_data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            config: Mutex::new(config),
+        })
+    }
+
+    pub async fn config(&self) -> Config {
+        self.config.lock().await.clone()
+    }
+
+    // Writes the manager's current controllers, servos and groups back to the config file
+    pub async fn persist(&self, servo_manager: &ServoManager) -> Result<(), HardwareError> {
+        let controllers = servo_manager.list_controllers().await;
+        let servos = servo_manager
+            .list_servos()
+            .await
+            .into_iter()
+            .map(|(name, status)| (name, status.config))
+            .collect();
+        let groups = servo_manager.list_groups().await;
+
+        let mut config = self.config.lock().await;
+        config.controllers = merge_entries(&config.controllers, controllers, |c| &c.id);
+        config.servos = merge_entries(&config.servos, servos, |s| &s.name);
+        config.groups = merge_entries(&config.groups, groups, |g| &g.name);
+
+        let data = serde_json::to_string_pretty(&*config)
+            .map_err(|e| HardwareError::Other(format!("Failed to serialize config: {}", e)))?;
+        write_atomic(&self.path, &data)?;
+
+        info!("Saved configuration to {}", self.path);
+        Ok(())
+    }
+}
+
+// Keeps the existing file order: updated entries stay in place, new ones are appended
+fn merge_entries<T: Clone>(
+    existing: &[T],
+    mut current: HashMap<String, T>,
+    key: impl Fn(&T) -> &String,
+) -> Vec<T> {
+    let mut merged: Vec<T> = existing
+        .iter()
+        .filter_map(|entry| current.remove(key(entry)))
+        .collect();
+
+    let mut added: Vec<T> = current.into_values().collect();
+    added.sort_by(|a, b| key(a).cmp(key(b)));
+    merged.extend(added);
+    merged
+}
+
+// Writes to a temporary file first so a crash never leaves a truncated file
+pub fn write_atomic(path: &str, data: &str) -> Result<(), HardwareError> {
+    let temp_path = format!("{}.tmp", path);
+    fs::write(&temp_path, data)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(names: &[&str]) -> HashMap<String, String> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn updated_entries_keep_their_place() {
+        let existing = vec!["dome".to_string(), "body".to_string()];
+        let merged = merge_entries(&existing, entries(&["body", "dome"]), |name| name);
+        assert_eq!(merged, vec!["dome", "body"]);
+    }
+
+    #[test]
+    fn new_entries_are_appended_in_name_order() {
+        let existing = vec!["dome".to_string()];
+        let merged = merge_entries(&existing, entries(&["zeta", "dome", "alpha"]), |name| name);
+        assert_eq!(merged, vec!["dome", "alpha", "zeta"]);
+    }
+
+    #[test]
+    fn deleted_entries_are_dropped() {
+        let existing = vec!["dome".to_string(), "body".to_string(), "arm".to_string()];
+        let merged = merge_entries(&existing, entries(&["arm", "dome"]), |name| name);
+        assert_eq!(merged, vec!["dome", "arm"]);
+    }
+
+    #[test]
+    fn entries_take_their_current_value() {
+        let existing = vec![("dome".to_string(), 40), ("body".to_string(), 41)];
+        let current = HashMap::from([
+            ("dome".to_string(), ("dome".to_string(), 42)),
+            ("body".to_string(), ("body".to_string(), 41)),
+        ]);
+        let merged = merge_entries(&existing, current, |(name, _)| name);
+        assert_eq!(
+            merged,
+            vec![("dome".to_string(), 42), ("body".to_string(), 41)]
+        );
+    }
+}
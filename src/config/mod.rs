@@ -1 +1,2 @@
+pub mod app_config;
 mod hardware_config;
@@ -0,0 +1,69 @@
+use crate::errors::hardware_error::HardwareError;
+use linux_embedded_hal::i2cdev::core::I2CDevice;
+use linux_embedded_hal::i2cdev::linux::{LinuxI2CBus, LinuxI2CDevice, LinuxI2CError};
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::ops::RangeInclusive;
+
+// Addresses probed by a scan; 0x00-0x07 are reserved by the I2C spec
+const SCAN_RANGE: RangeInclusive<u8> = 0x08..=0x7F;
+// Addresses a PCA9685 can answer on, set by its A0-A5 jumpers
+pub const PCA9685_RANGE: RangeInclusive<u8> = 0x40..=0x7F;
+
+#[derive(Serialize)]
+pub struct ScannedDevice {
+    pub address: String, // Hex string like "0x40", matching the controller config
+    pub pca9685_range: bool,
+    pub in_use: bool, // Claimed by a kernel driver, so it was not probed
+    pub controller: Option<String>, // Configured controller at this address
+}
+
+#[derive(Serialize)]
+pub struct BusScan {
+    pub bus: String,
+    pub devices: Vec<ScannedDevice>,
+    pub missing_controllers: Vec<String>, // Configured on this bus but not answering
+}
+
+// Probes every address on the bus the way i2cdetect does. Blocking, run it off the runtime
+pub fn probe_bus(bus: &str) -> Result<Vec<(u8, bool)>, HardwareError> {
+    LinuxI2CBus::new(bus).map_err(|e| match e {
+        LinuxI2CError::Io(e) if e.kind() == ErrorKind::NotFound => {
+            HardwareError::NotFound(format!("I2C bus {} not found", bus))
+        }
+        e => HardwareError::CommunicationError(format!("Failed to open I2C bus {}: {}", bus, e)),
+    })?;
+
+    let mut found = Vec::new();
+    for address in SCAN_RANGE {
+        match LinuxI2CDevice::new(bus, address as u16) {
+            Ok(mut device) => {
+                if probe(&mut device, address) {
+                    found.push((address, false));
+                }
+            }
+            Err(LinuxI2CError::Errno(errno))
+                if std::io::Error::from_raw_os_error(errno).kind() == ErrorKind::ResourceBusy =>
+            {
+                found.push((address, true));
+            }
+            Err(e) => {
+                return Err(HardwareError::CommunicationError(format!(
+                    "Failed to probe {:#04x} on {}: {}",
+                    address, bus, e
+                )))
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+fn probe(device: &mut LinuxI2CDevice, address: u8) -> bool {
+    // A quick write can corrupt EEPROMs, so those ranges get a read instead
+    if (0x30..=0x37).contains(&address) || (0x50..=0x5F).contains(&address) {
+        device.smbus_read_byte().is_ok()
+    } else {
+        device.smbus_write_quick(false).is_ok()
+    }
+}
@@ -0,0 +1,90 @@
+use crate::hardware::servo::config::PWM_RESOLUTION;
+use serde::{Deserialize, Serialize};
+
+// Usual correction so equal brightness steps look equal to the eye
+const DEFAULT_GAMMA: f64 = 2.2;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct LedConfig {
+    pub name: String,
+    pub controller_id: String, // References Pca9685Config id
+    pub channel: u8,
+    #[serde(default = "default_gamma")]
+    pub gamma: f64, // Brightness is raised to this power before becoming a duty cycle
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl LedConfig {
+    // Duty cycle in 12-bit ticks for a brightness in percent
+    pub fn duty_ticks(&self, brightness: f64) -> u16 {
+        let level = (brightness / 100.0).clamp(0.0, 1.0).powf(self.gamma);
+        (level * (PWM_RESOLUTION - 1.0)).round() as u16
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct BlinkPattern {
+    pub on_ms: u64,
+    pub off_ms: u64,
+    #[serde(default)]
+    pub count: Option<u32>, // Blinks until told otherwise when omitted
+    #[serde(default = "full_brightness")]
+    pub brightness: f64, // Percent while on
+}
+
+fn default_gamma() -> f64 {
+    DEFAULT_GAMMA
+}
+
+fn full_brightness() -> f64 {
+    100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn led(gamma: Option<f64>) -> LedConfig {
+        let mut config = json!({
+            "name": "Front Holoprojector",
+            "controller_id": "dome",
+            "channel": 15
+        });
+        if let Some(gamma) = gamma {
+            config["gamma"] = json!(gamma);
+        }
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn the_ends_of_the_range_are_off_and_full() {
+        let config = led(None);
+        assert_eq!(config.duty_ticks(0.0), 0);
+        assert_eq!(config.duty_ticks(100.0), 4095);
+    }
+
+    #[test]
+    fn gamma_darkens_the_middle_of_the_range() {
+        let config = led(None);
+        assert_eq!(config.gamma, DEFAULT_GAMMA);
+        assert_eq!(config.duty_ticks(50.0), 891);
+        assert_eq!(config.duty_ticks(10.0), 26);
+        // Dim enough to round down to dark
+        assert_eq!(config.duty_ticks(1.0), 0);
+    }
+
+    #[test]
+    fn a_gamma_of_one_is_linear() {
+        let config = led(Some(1.0));
+        assert_eq!(config.duty_ticks(50.0), 2048);
+    }
+
+    #[test]
+    fn brightness_outside_the_range_is_clamped() {
+        let config = led(None);
+        assert_eq!(config.duty_ticks(-20.0), 0);
+        assert_eq!(config.duty_ticks(150.0), 4095);
+    }
+}
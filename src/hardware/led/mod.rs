@@ -1 +1,4 @@
-mod controller;
+pub(crate) mod config;
+pub(crate) mod state;
+
+pub use config::{BlinkPattern, LedConfig};
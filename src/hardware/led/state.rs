@@ -0,0 +1,45 @@
+use crate::hardware::led::config::LedConfig;
+use crate::hardware::servo::motion::Sweep;
+use serde::Serialize;
+use std::time::Instant;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LedEffect {
+    #[default]
+    Steady,
+    Fading,
+    Blinking,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct LedState {
+    pub brightness: f64, // Percent, as last written or partway through a fade
+    pub effect: LedEffect,
+    pub last_change_ms: Option<u64>, // Unix timestamp of the last write
+    pub last_error: Option<String>,
+    #[serde(skip)]
+    pub(crate) generation: u64, // Bumped on every command so a running effect knows it was replaced
+    #[serde(skip)]
+    pub(crate) fade: Option<Sweep>, // Fade the motion loop is running
+}
+
+impl LedState {
+    // Brightness right now, following a fade in progress
+    pub fn current(&self) -> Self {
+        let brightness = self
+            .fade
+            .map_or(self.brightness, |fade| fade.value_at(Instant::now()));
+        Self {
+            brightness,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct LedStatus {
+    #[serde(flatten)]
+    pub config: LedConfig,
+    pub state: LedState,
+}
@@ -1,3 +1,5 @@
 pub mod audio;
-mod led;
+pub mod i2c;
+pub mod led;
+pub mod output;
 pub mod servo;
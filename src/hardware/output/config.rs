@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DigitalOutputConfig {
+    pub name: String,
+    pub controller_id: String, // References Pca9685Config id
+    pub channel: u8,
+    #[serde(default)]
+    pub active_low: bool, // For relay boards that switch on when the input is pulled low
+    #[serde(default)]
+    pub default_on: bool, // Safe state, applied at startup and on emergency stop
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl DigitalOutputConfig {
+    // Whether the channel has to be driven high for the output to be `on`
+    pub fn channel_high(&self, on: bool) -> bool {
+        on != self.active_low
+    }
+}
@@ -0,0 +1,4 @@
+pub(crate) mod config;
+pub(crate) mod state;
+
+pub use config::DigitalOutputConfig;
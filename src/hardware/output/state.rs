@@ -0,0 +1,19 @@
+use crate::hardware::output::config::DigitalOutputConfig;
+use serde::Serialize;
+
+#[derive(Clone, Default, Serialize)]
+pub struct DigitalOutputState {
+    pub on: bool,
+    pub pulse_until_ms: Option<u64>, // Unix timestamp a momentary pulse ends at
+    pub last_change_ms: Option<u64>, // Unix timestamp of the last write
+    pub last_error: Option<String>,
+    #[serde(skip)]
+    pub(crate) generation: u64, // Bumped on every command so a running pulse knows it was replaced
+}
+
+#[derive(Clone, Serialize)]
+pub struct DigitalOutputStatus {
+    #[serde(flatten)]
+    pub config: DigitalOutputConfig,
+    pub state: DigitalOutputState,
+}
@@ -0,0 +1,369 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::health::HealthStatus;
+use crate::hardware::servo::simulated::SimulatedBoard;
+use crate::hardware::servo::Pca9685Config;
+use crate::traits::hardware::{PwmBackend, PwmDriver};
+use async_trait::async_trait;
+use log::debug;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+
+// Past this many waiting requests, senders wait for room instead of queueing more
+const QUEUE_CAPACITY: usize = 256;
+
+#[derive(Clone, Copy)]
+enum Output {
+    Pulse(u16),
+    FullOff,
+    FullOn,
+}
+
+enum Command {
+    Write(Vec<(u8, Output)>),
+    CheckHealth,
+}
+
+struct Request {
+    command: Command,
+    queued_at: Instant,
+    reply: oneshot::Sender<Result<(), HardwareError>>,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct ControllerMetrics {
+    pub queue_depth: usize, // Requests waiting for the controller task right now
+    pub max_queue_depth: usize, // Most requests picked up by the task at once
+    pub requests: u64,
+    pub coalesced: u64, // Channel values replaced by a newer one before reaching the board
+    pub writes: u64,    // Transactions actually sent to the board
+    pub health_checks: u64, // Probes of the board, apart from the writes
+    pub last_latency_us: u64, // From queueing a request to its write finishing
+    pub avg_latency_us: u64,
+    pub max_latency_us: u64,
+    #[serde(skip)]
+    total_latency_us: u64,
+}
+
+struct Shared {
+    health: Mutex<HealthStatus>,
+    metrics: Mutex<ControllerMetrics>,
+}
+
+// Front end of a board owned by its own task on its own thread. Writes from every manager
+// queue up on the task's channel, and the blocking I2C calls only ever stall that thread, so a
+// slow board only holds up writes to itself
+pub struct ControllerHandle {
+    config: Pca9685Config,
+    frequency: f64,
+    simulated: Option<SimulatedBoard>,
+    sender: mpsc::Sender<Request>,
+    shared: Arc<Shared>,
+}
+
+impl ControllerHandle {
+    // Hands the board to a new thread, which runs until the handle is dropped
+    pub fn spawn(backend: Box<dyn PwmBackend>) -> Result<Self, HardwareError> {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let shared = Arc::new(Shared {
+            health: Mutex::new(backend.health()),
+            metrics: Mutex::new(ControllerMetrics::default()),
+        });
+
+        let handle = Self {
+            config: backend.get_config().clone(),
+            frequency: backend.frequency(),
+            simulated: backend.simulated(),
+            sender,
+            shared: shared.clone(),
+        };
+
+        // A thread of its own keeps blocking I2C calls off the server's workers and away from
+        // the other boards; it only needs a small runtime for the retry backoff timers
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .map_err(|e| spawn_failed(&handle.config.id, e))?;
+        thread::Builder::new()
+            .name(format!("controller-{}", handle.config.id))
+            .spawn(move || runtime.block_on(run(backend, receiver, shared)))
+            .map_err(|e| spawn_failed(&handle.config.id, e))?;
+
+        Ok(handle)
+    }
+
+    async fn send(&self, command: Command) -> Result<(), HardwareError> {
+        let (reply, response) = oneshot::channel();
+        let request = Request {
+            command,
+            queued_at: Instant::now(),
+            reply,
+        };
+
+        self.sender
+            .send(request)
+            .await
+            .map_err(|_| stopped(&self.config.id))?;
+        response.await.map_err(|_| stopped(&self.config.id))?
+    }
+
+    async fn write(&self, outputs: Vec<(u8, Output)>) -> Result<(), HardwareError> {
+        // Checked here so one bad channel can't fail the writes it gets coalesced with
+        if let Some((channel, _)) = outputs.iter().find(|(channel, _)| *channel > 15) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Invalid channel number: {}",
+                channel
+            )));
+        }
+        self.send(Command::Write(outputs)).await
+    }
+}
+
+#[async_trait]
+impl PwmDriver for ControllerHandle {
+    fn get_config(&self) -> &Pca9685Config {
+        &self.config
+    }
+
+    fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    fn health(&self) -> HealthStatus {
+        self.shared.health.lock().unwrap().clone()
+    }
+
+    fn simulated(&self) -> Option<SimulatedBoard> {
+        self.simulated.clone()
+    }
+
+    fn metrics(&self) -> ControllerMetrics {
+        let mut metrics = self.shared.metrics.lock().unwrap().clone();
+        metrics.queue_depth = self.sender.max_capacity() - self.sender.capacity();
+        metrics
+    }
+
+    async fn check_health(&self) -> Result<(), HardwareError> {
+        self.send(Command::CheckHealth).await
+    }
+
+    async fn set_pulse(&self, channel: u8, pulse_width: u16) -> Result<(), HardwareError> {
+        self.write(vec![(channel, Output::Pulse(pulse_width))])
+            .await
+    }
+
+    async fn set_pulses(&self, pulses: &[(u8, u16)]) -> Result<(), HardwareError> {
+        let outputs = pulses
+            .iter()
+            .map(|&(channel, pulse_width)| (channel, Output::Pulse(pulse_width)))
+            .collect();
+        self.write(outputs).await
+    }
+
+    async fn set_full_off(&self, channel: u8) -> Result<(), HardwareError> {
+        self.write(vec![(channel, Output::FullOff)]).await
+    }
+
+    async fn set_full_on(&self, channel: u8) -> Result<(), HardwareError> {
+        self.write(vec![(channel, Output::FullOn)]).await
+    }
+}
+
+async fn run(
+    mut backend: Box<dyn PwmBackend>,
+    mut receiver: mpsc::Receiver<Request>,
+    shared: Arc<Shared>,
+) {
+    while let Some(request) = receiver.recv().await {
+        // Everything that queued up during the last write goes out together
+        let mut requests = vec![request];
+        while let Ok(request) = receiver.try_recv() {
+            requests.push(request);
+        }
+
+        let health_check = if requests
+            .iter()
+            .any(|request| matches!(request.command, Command::CheckHealth))
+        {
+            Some(backend.check_health().await)
+        } else {
+            None
+        };
+
+        // Latest value wins on each channel. A request whose value was replaced gets the
+        // outcome of the write that replaced it
+        let mut latest = BTreeMap::new();
+        let mut coalesced = 0;
+        for request in &requests {
+            if let Command::Write(outputs) = &request.command {
+                for &(channel, output) in outputs {
+                    if latest.insert(channel, output).is_some() {
+                        coalesced += 1;
+                    }
+                }
+            }
+        }
+        let (failures, writes) = flush(backend.as_mut(), &latest).await;
+
+        *shared.health.lock().unwrap() = backend.health();
+
+        let mut metrics = shared.metrics.lock().unwrap();
+        metrics.max_queue_depth = metrics.max_queue_depth.max(requests.len());
+        metrics.requests += requests.len() as u64;
+        metrics.coalesced += coalesced;
+        metrics.writes += writes;
+        metrics.health_checks += health_check.is_some() as u64;
+        if coalesced > 0 {
+            debug!(
+                "Controller '{}': {} requests coalesced into {} writes",
+                backend.get_config().id,
+                requests.len(),
+                writes
+            );
+        }
+
+        for request in requests {
+            let result = match &request.command {
+                Command::CheckHealth => match &health_check {
+                    Some(Err(e)) => Err(duplicate(e)),
+                    _ => Ok(()),
+                },
+                Command::Write(outputs) => outputs
+                    .iter()
+                    .find_map(|(channel, _)| failures.get(channel))
+                    .map_or(Ok(()), |e| Err(duplicate(e))),
+            };
+
+            let latency_us = request.queued_at.elapsed().as_micros() as u64;
+            metrics.last_latency_us = latency_us;
+            metrics.max_latency_us = metrics.max_latency_us.max(latency_us);
+            metrics.total_latency_us += latency_us;
+            metrics.avg_latency_us = metrics.total_latency_us / metrics.requests;
+
+            // The sender may have given up waiting
+            let _ = request.reply.send(result);
+        }
+    }
+
+    debug!("Controller '{}' task stopped", backend.get_config().id);
+}
+
+// Writes the coalesced outputs, every pulse in one transaction. Returns the error of each
+// channel that failed and the number of transactions sent
+async fn flush(
+    backend: &mut dyn PwmBackend,
+    outputs: &BTreeMap<u8, Output>,
+) -> (BTreeMap<u8, HardwareError>, u64) {
+    let mut failures = BTreeMap::new();
+    let mut writes = 0;
+
+    let pulses: Vec<(u8, u16)> = outputs
+        .iter()
+        .filter_map(|(&channel, output)| match output {
+            Output::Pulse(pulse_width) => Some((channel, *pulse_width)),
+            _ => None,
+        })
+        .collect();
+    let result = match pulses.as_slice() {
+        [] => Ok(()),
+        [(channel, pulse_width)] => backend.set_pulse(*channel, *pulse_width).await,
+        _ => backend.set_pulses(&pulses).await,
+    };
+    if !pulses.is_empty() {
+        writes += 1;
+    }
+    if let Err(e) = result {
+        for (channel, _) in &pulses {
+            failures.insert(*channel, duplicate(&e));
+        }
+    }
+
+    for (&channel, output) in outputs {
+        let result = match output {
+            Output::FullOff => backend.set_full_off(channel).await,
+            Output::FullOn => backend.set_full_on(channel).await,
+            Output::Pulse(_) => continue,
+        };
+        writes += 1;
+        if let Err(e) = result {
+            failures.insert(channel, e);
+        }
+    }
+
+    (failures, writes)
+}
+
+// Each waiter needs its own copy, and io::Error can't be cloned
+pub(crate) fn duplicate(error: &HardwareError) -> HardwareError {
+    match error {
+        HardwareError::NotFound(message) => HardwareError::NotFound(message.clone()),
+        HardwareError::InitializationError(message) => {
+            HardwareError::InitializationError(message.clone())
+        }
+        HardwareError::CommunicationError(message) => {
+            HardwareError::CommunicationError(message.clone())
+        }
+        HardwareError::InvalidParameter(message) => {
+            HardwareError::InvalidParameter(message.clone())
+        }
+        HardwareError::Busy(message) => HardwareError::Busy(message.clone()),
+        HardwareError::InvalidState(message) => HardwareError::InvalidState(message.clone()),
+        HardwareError::Timeout(message) => HardwareError::Timeout(message.clone()),
+        HardwareError::IoError(e) => HardwareError::CommunicationError(e.to_string()),
+        HardwareError::Other(message) => HardwareError::Other(message.clone()),
+    }
+}
+
+fn spawn_failed(id: &str, error: std::io::Error) -> HardwareError {
+    HardwareError::InitializationError(format!(
+        "Failed to start the task for controller '{}': {}",
+        id, error
+    ))
+}
+
+fn stopped(id: &str) -> HardwareError {
+    HardwareError::CommunicationError(format!("Controller '{}' task has stopped", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::servo::simulated::SimulatedPwmDriver;
+
+    fn handle() -> ControllerHandle {
+        let config = serde_json::from_value(serde_json::json!({
+            "id": "dome",
+            "i2c_address": "0x40",
+            "frequency": 50,
+            "driver": "simulated"
+        }))
+        .unwrap();
+        ControllerHandle::spawn(Box::new(SimulatedPwmDriver::new(config).unwrap())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn health_checks_are_not_counted_as_writes() {
+        let handle = handle();
+        handle.check_health().await.unwrap();
+        handle.check_health().await.unwrap();
+        handle.set_pulses(&[(0, 300), (1, 300)]).await.unwrap();
+
+        let metrics = handle.metrics();
+        assert_eq!(metrics.requests, 3);
+        assert_eq!(metrics.health_checks, 2);
+        assert_eq!(metrics.writes, 1);
+    }
+
+    #[tokio::test]
+    async fn a_bad_channel_only_fails_its_own_request() {
+        let handle = handle();
+        assert!(handle.set_pulse(16, 300).await.is_err());
+        handle.set_pulse(0, 300).await.unwrap();
+
+        let board = handle.simulated().unwrap();
+        assert_eq!(board.channels()[0], Some(300));
+    }
+}
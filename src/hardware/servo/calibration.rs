@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationMark {
+    Min,
+    Max,
+    Home,
+}
+
+// Pulses found so far while calibrating a servo, all in 12-bit ticks
+#[derive(Clone, Default, Serialize)]
+pub struct CalibrationSession {
+    pub pulse: Option<u16>, // Pulse currently being driven
+    pub min_pulse: Option<u16>,
+    pub max_pulse: Option<u16>,
+    pub home_pulse: Option<u16>,
+}
+
+impl CalibrationSession {
+    pub fn mark(&mut self, mark: CalibrationMark, pulse: u16) {
+        match mark {
+            CalibrationMark::Min => self.min_pulse = Some(pulse),
+            CalibrationMark::Max => self.max_pulse = Some(pulse),
+            CalibrationMark::Home => self.home_pulse = Some(pulse),
+        }
+    }
+}
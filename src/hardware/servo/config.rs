@@ -1,10 +1,222 @@
+use crate::errors::hardware_error::HardwareError;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// Bus used by controllers that don't name one
+const DEFAULT_I2C_BUS: &str = "/dev/i2c-1";
+// Address of a PCA9685 with no address jumpers bridged
+const DEFAULT_I2C_ADDRESS: u8 = 0x40;
+// Nominal frequency of the PCA9685's internal oscillator
+const DEFAULT_OSCILLATOR_HZ: u32 = 25_000_000;
+// Counter steps per PWM period (12-bit)
+pub const PWM_RESOLUTION: f64 = 4096.0;
+
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DriverKind {
+    #[default]
+    Pca9685,
+    Simulated,
+}
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Pca9685Config {
     pub id: String,
+    #[serde(default = "default_i2c_bus")]
+    pub bus: String, // Device path like "/dev/i2c-3", or just the bus number
     pub i2c_address: String, // Hex string like "0x40"
     pub frequency: u16,      // PWM frequency in Hz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oscillator_hz: Option<u32>, // Measured oscillator frequency, defaults to 25 MHz
+    #[serde(default)]
+    pub driver: DriverKind,
+    #[serde(default, skip_serializing_if = "RetryConfig::is_default")]
+    pub retry: RetryConfig,
+}
+
+// How hard a controller tries before a write is reported as failed
+#[derive(Clone, Deserialize, Serialize, PartialEq)]
+pub struct RetryConfig {
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32, // Retries after the first try
+    #[serde(default = "default_retry_backoff_ms")]
+    pub backoff_ms: u64, // Wait before the first retry, doubled for each one after
+    #[serde(default = "default_retry_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    #[serde(default = "default_offline_after")]
+    pub offline_after: u32, // Failed operations in a row before the controller counts as offline
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: default_retry_attempts(),
+            backoff_ms: default_retry_backoff_ms(),
+            max_backoff_ms: default_retry_max_backoff_ms(),
+            offline_after: default_offline_after(),
+        }
+    }
+}
+
+impl RetryConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Wait before retry number `retry`, counting from 1
+    pub fn backoff(&self, retry: u32) -> u64 {
+        let factor = 1u64 << (retry.saturating_sub(1)).min(16);
+        self.backoff_ms
+            .saturating_mul(factor)
+            .min(self.max_backoff_ms)
+    }
+}
+
+// The loop that advances every servo move and LED fade, writing what changed once per tick
+#[derive(Clone, Deserialize, Serialize)]
+pub struct MotionConfig {
+    #[serde(default = "default_tick_rate_hz")]
+    pub tick_rate_hz: f64,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate_hz: default_tick_rate_hz(),
+        }
+    }
+}
+
+impl MotionConfig {
+    pub fn tick_interval(&self) -> Result<Duration, HardwareError> {
+        if !(1.0..=1000.0).contains(&self.tick_rate_hz) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Motion tick rate {} Hz is outside valid range [1, 1000]",
+                self.tick_rate_hz
+            )));
+        }
+        Ok(Duration::from_secs_f64(1.0 / self.tick_rate_hz))
+    }
+}
+
+// I2C trace recording, for looking back at what the boards were sent
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TraceConfig {
+    #[serde(default = "default_trace_directory")]
+    pub directory: String, // Every trace path, from here or the API, is relative to this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_path: Option<String>, // Recorded from startup when set
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            directory: default_trace_directory(),
+            record_path: None,
+        }
+    }
+}
+
+fn default_trace_directory() -> String {
+    "traces".to_string()
+}
+
+impl Pca9685Config {
+    pub fn bus_path(&self) -> String {
+        i2c_bus_path(&self.bus)
+    }
+
+    // 7-bit I2C address; "default" is the board's factory address
+    pub fn address(&self) -> Result<u8, HardwareError> {
+        if self.i2c_address == "default" {
+            return Ok(DEFAULT_I2C_ADDRESS);
+        }
+
+        u8::from_str_radix(self.i2c_address.trim_start_matches("0x"), 16)
+            .ok()
+            .filter(|address| *address < 0x80)
+            .ok_or_else(|| {
+                HardwareError::InvalidParameter(format!(
+                    "Invalid I2C address: {}",
+                    self.i2c_address
+                ))
+            })
+    }
+
+    pub fn oscillator_hz(&self) -> u32 {
+        self.oscillator_hz.unwrap_or(DEFAULT_OSCILLATOR_HZ)
+    }
+
+    // Prescale register value for the configured frequency (datasheet section 7.3.5)
+    pub fn prescale(&self) -> Result<u8, HardwareError> {
+        if self.frequency == 0 {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Controller '{}' has a frequency of 0 Hz",
+                self.id
+            )));
+        }
+
+        let prescale =
+            (self.oscillator_hz() as f64 / (PWM_RESOLUTION * self.frequency as f64)).round() - 1.0;
+
+        if !(3.0..=255.0).contains(&prescale) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Frequency {} Hz is out of range for controller '{}'",
+                self.frequency, self.id
+            )));
+        }
+
+        Ok(prescale as u8)
+    }
+
+    // Frequency the chip really runs at once the prescale has been rounded
+    pub fn actual_frequency(&self) -> Result<f64, HardwareError> {
+        let prescale = self.prescale()?;
+        Ok(self.oscillator_hz() as f64 / (PWM_RESOLUTION * (prescale as f64 + 1.0)))
+    }
+}
+
+fn default_i2c_bus() -> String {
+    DEFAULT_I2C_BUS.to_string()
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    5
+}
+
+fn default_retry_max_backoff_ms() -> u64 {
+    100
+}
+
+fn default_offline_after() -> u32 {
+    3
+}
+
+// One step per servo frame
+fn default_tick_rate_hz() -> f64 {
+    50.0
+}
+
+// Accepts either a device path or a bare bus number ("3" -> "/dev/i2c-3")
+pub fn i2c_bus_path(bus: &str) -> String {
+    if !bus.is_empty() && bus.chars().all(|c| c.is_ascii_digit()) {
+        format!("/dev/i2c-{}", bus)
+    } else {
+        bus.to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServoKind {
+    #[default]
+    Positional, // Pulse sets the angle
+    Continuous, // Pulse sets speed and direction around a neutral pulse
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -12,10 +224,289 @@ pub struct ServoConfig {
     pub name: String,
     pub controller_id: String, // References Pca9685Config id
     pub channel: u8,
-    pub min_angle: f64,
+    #[serde(default)]
+    pub kind: ServoKind,
+    #[serde(default)]
+    pub min_angle: f64, // Unused by continuous servos
+    #[serde(default)]
     pub max_angle: f64,
-    pub min_pulse: u16,
-    pub max_pulse: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_pulse: Option<u16>, // 12-bit ticks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pulse: Option<u16>, // 12-bit ticks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_pulse_us: Option<f64>, // Microseconds, takes precedence over min_pulse
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pulse_us: Option<f64>, // Microseconds, takes precedence over max_pulse
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neutral_pulse: Option<u16>, // Continuous only: stopped, defaults to mid-range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neutral_pulse_us: Option<f64>, // Takes precedence over neutral_pulse
+    #[serde(default)]
+    pub deadband: u16, // Continuous only: ticks either side of neutral that don't turn the servo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home_angle: Option<f64>, // Angle the servo returns to when homed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub positions: BTreeMap<String, f64>, // Named angles like "open" and "closed"
+    #[serde(default)]
+    pub inverted: bool, // Mirrors the angle range, for servos mounted the other way round
+    #[serde(default)]
+    pub trim_degrees: f64, // Offset added after inversion to correct horn misalignment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_speed_deg_per_s: Option<f64>, // Moves are stretched so they never go faster
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_accel: Option<f64>, // Degrees per second squared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_relax_after_ms: Option<u64>, // Stop driving the servo once it has been still this long
     #[serde(default)]
     pub description: Option<String>,
 }
+
+impl ServoConfig {
+    pub fn resolve(&self, target: &ServoTarget) -> Result<f64, HardwareError> {
+        match target {
+            ServoTarget::Angle(angle) => Ok(*angle),
+            ServoTarget::Named(position) => {
+                self.positions.get(position).copied().ok_or_else(|| {
+                    HardwareError::InvalidParameter(format!(
+                        "Servo '{}' has no position named '{}'",
+                        self.name, position
+                    ))
+                })
+            }
+        }
+    }
+
+    // Resolves the pulse range in ticks for a controller running at `frequency` Hz
+    pub fn pulse_range(&self, frequency: f64) -> Result<(u16, u16), HardwareError> {
+        let min_pulse = match self.min_pulse_us {
+            Some(us) => us_to_ticks(us, frequency),
+            None => self.min_pulse.ok_or_else(|| self.missing_pulse("min"))?,
+        };
+        let max_pulse = match self.max_pulse_us {
+            Some(us) => us_to_ticks(us, frequency),
+            None => self.max_pulse.ok_or_else(|| self.missing_pulse("max"))?,
+        };
+
+        if min_pulse >= max_pulse || max_pulse >= PWM_RESOLUTION as u16 {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Servo '{}' has an invalid pulse range [{}, {}]",
+                self.name, min_pulse, max_pulse
+            )));
+        }
+
+        Ok((min_pulse, max_pulse))
+    }
+
+    // Pulse at which a continuous servo stands still, in ticks
+    pub fn neutral_pulse(&self, frequency: f64) -> Result<u16, HardwareError> {
+        let (min_pulse, max_pulse) = self.pulse_range(frequency)?;
+        let neutral = match (self.neutral_pulse_us, self.neutral_pulse) {
+            (Some(us), _) => us_to_ticks(us, frequency),
+            (None, Some(ticks)) => ticks,
+            (None, None) => min_pulse + (max_pulse - min_pulse) / 2,
+        };
+
+        if neutral <= min_pulse || neutral >= max_pulse {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Servo '{}' neutral pulse {} is outside its pulse range [{}, {}]",
+                self.name, neutral, min_pulse, max_pulse
+            )));
+        }
+
+        Ok(neutral)
+    }
+
+    // Ticks depend on the controller frequency, microseconds don't
+    pub fn uses_raw_ticks(&self) -> bool {
+        (self.min_pulse_us.is_none() && self.min_pulse.is_some())
+            || (self.max_pulse_us.is_none() && self.max_pulse.is_some())
+    }
+
+    fn missing_pulse(&self, bound: &str) -> HardwareError {
+        HardwareError::InvalidParameter(format!(
+            "Servo '{}' needs either {}_pulse or {}_pulse_us",
+            self.name, bound, bound
+        ))
+    }
+}
+
+// Where to move a servo: an angle, or one of its named positions
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ServoTarget {
+    Angle(f64),
+    Named(String),
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ServoGroupConfig {
+    pub name: String,
+    pub servos: Vec<String>, // References ServoConfig names
+    #[serde(default)]
+    pub stagger_ms: u64, // Delay between successive members starting to move
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PoseConfig {
+    #[serde(default = "default_poses_file")]
+    pub file: String, // JSON file poses are saved to
+}
+
+impl Default for PoseConfig {
+    fn default() -> Self {
+        Self {
+            file: default_poses_file(),
+        }
+    }
+}
+
+fn default_poses_file() -> String {
+    "poses.json".to_string()
+}
+
+pub fn us_to_ticks(us: f64, frequency: f64) -> u16 {
+    (us * frequency * PWM_RESOLUTION / 1_000_000.0).round() as u16
+}
+
+// `servo` may leave `free_range` only while `requires_servo` is within `requires_range`
+#[derive(Clone, Deserialize, Serialize)]
+pub struct InterlockConfig {
+    pub name: String,
+    pub servo: String,
+    pub free_range: [f64; 2], // Degrees, inclusive
+    pub requires_servo: String,
+    pub requires_range: [f64; 2],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_sequence: Option<ServoTarget>, // Moves `requires_servo` here first instead of failing
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn controller(frequency: u16) -> Pca9685Config {
+        serde_json::from_value(json!({
+            "id": "dome",
+            "i2c_address": "0x40",
+            "frequency": frequency
+        }))
+        .unwrap()
+    }
+
+    fn servo(pulses: serde_json::Value) -> ServoConfig {
+        let mut config = json!({
+            "name": "Pie Panel 1",
+            "controller_id": "dome",
+            "channel": 0,
+            "min_angle": 0,
+            "max_angle": 90
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(pulses.as_object().unwrap().clone());
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn prescale_follows_the_configured_frequency() {
+        assert_eq!(controller(50).prescale().unwrap(), 121);
+        assert_eq!(controller(60).prescale().unwrap(), 101);
+        assert_eq!(controller(1000).prescale().unwrap(), 5);
+
+        let actual = controller(50).actual_frequency().unwrap();
+        assert!((actual - 50.0).abs() < 0.1, "{}", actual);
+    }
+
+    #[test]
+    fn prescale_uses_a_measured_oscillator() {
+        let mut config = controller(50);
+        config.oscillator_hz = Some(26_000_000);
+        assert_eq!(config.prescale().unwrap(), 126);
+    }
+
+    #[test]
+    fn prescale_rejects_frequencies_the_chip_cannot_reach() {
+        assert!(controller(0).prescale().is_err());
+        assert!(controller(2000).prescale().is_err());
+        assert!(controller(10).prescale().is_err());
+    }
+
+    #[test]
+    fn microseconds_convert_to_ticks_at_the_given_frequency() {
+        assert_eq!(us_to_ticks(1500.0, 50.0), 307);
+        assert_eq!(us_to_ticks(1000.0, 50.0), 205);
+        assert_eq!(us_to_ticks(0.0, 50.0), 0);
+        // The same pulse is fewer ticks at a lower frequency
+        assert!(us_to_ticks(1500.0, 50.0) < us_to_ticks(1500.0, 60.0));
+    }
+
+    #[test]
+    fn pulse_range_prefers_microseconds_over_ticks() {
+        let config = servo(json!({
+            "min_pulse": 150,
+            "max_pulse": 600,
+            "min_pulse_us": 1000,
+            "max_pulse_us": 2000
+        }));
+        assert_eq!(config.pulse_range(50.0).unwrap(), (205, 410));
+        assert!(!config.uses_raw_ticks());
+
+        let ticks = servo(json!({"min_pulse": 150, "max_pulse": 600}));
+        assert_eq!(ticks.pulse_range(50.0).unwrap(), (150, 600));
+        assert!(ticks.uses_raw_ticks());
+    }
+
+    #[test]
+    fn pulse_range_needs_both_ends_in_order() {
+        assert!(servo(json!({"min_pulse": 150})).pulse_range(50.0).is_err());
+        assert!(servo(json!({"min_pulse": 600, "max_pulse": 150}))
+            .pulse_range(50.0)
+            .is_err());
+        assert!(servo(json!({"min_pulse": 150, "max_pulse": 4096}))
+            .pulse_range(50.0)
+            .is_err());
+    }
+
+    #[test]
+    fn retry_backoff_doubles_from_the_first_retry() {
+        let retry = RetryConfig::default();
+        assert_eq!(retry.backoff(1), 5);
+        assert_eq!(retry.backoff(2), 10);
+        assert_eq!(retry.backoff(3), 20);
+    }
+
+    #[test]
+    fn retry_backoff_is_capped() {
+        let retry = RetryConfig {
+            backoff_ms: 10,
+            max_backoff_ms: 50,
+            ..RetryConfig::default()
+        };
+        assert_eq!(retry.backoff(3), 40);
+        assert_eq!(retry.backoff(4), 50);
+        assert_eq!(retry.backoff(64), 50);
+
+        let retry = RetryConfig {
+            backoff_ms: u64::MAX,
+            ..RetryConfig::default()
+        };
+        assert_eq!(retry.backoff(2), retry.max_backoff_ms);
+    }
+
+    #[test]
+    fn retry_settings_default_field_by_field() {
+        let mut config = controller(50);
+        config.retry = serde_json::from_value(json!({ "attempts": 5 })).unwrap();
+        assert_eq!(config.retry.attempts, 5);
+        assert_eq!(config.retry.backoff_ms, RetryConfig::default().backoff_ms);
+        assert!(!config.retry.is_default());
+        assert!(controller(50).retry.is_default());
+    }
+}
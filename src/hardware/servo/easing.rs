@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Cubic,
+    Bounce,
+}
+
+impl Easing {
+    // Maps linear progress in [0, 1] onto the eased curve, also in [0, 1]
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
+                }
+            }
+            Easing::Cubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    let f = 2.0 * t - 2.0;
+                    0.5 * f * f * f + 1.0
+                }
+            }
+            Easing::Bounce => bounce_out(t),
+        }
+    }
+
+    // Steepest slope of the curve, i.e. top speed relative to a linear move over the same time
+    pub fn peak_velocity(&self) -> f64 {
+        match self {
+            Easing::Linear => 1.0,
+            Easing::EaseIn | Easing::EaseOut | Easing::EaseInOut => 2.0,
+            Easing::Cubic => 3.0,
+            Easing::Bounce => 5.5,
+        }
+    }
+
+    // Largest curvature, in units of distance / duration². None where the velocity jumps
+    pub fn peak_acceleration(&self) -> Option<f64> {
+        match self {
+            Easing::Linear | Easing::Bounce => None,
+            Easing::EaseIn | Easing::EaseOut => Some(2.0),
+            Easing::EaseInOut => Some(4.0),
+            Easing::Cubic => Some(12.0),
+        }
+    }
+}
+
+// Standard ease-out bounce, settling on the target like a dropped ball
+fn bounce_out(t: f64) -> f64 {
+    const N: f64 = 7.5625;
+    const D: f64 = 2.75;
+
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Easing; 6] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::Cubic,
+        Easing::Bounce,
+    ];
+
+    #[test]
+    fn every_curve_starts_at_zero_and_ends_at_one() {
+        for easing in ALL {
+            assert!(easing.apply(0.0).abs() < 1e-9, "{:?} at 0", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9, "{:?} at 1", easing);
+        }
+    }
+
+    #[test]
+    fn progress_outside_the_move_is_clamped() {
+        for easing in ALL {
+            assert_eq!(easing.apply(-0.5), easing.apply(0.0));
+            assert_eq!(easing.apply(1.5), easing.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn curves_are_symmetric_or_skewed_as_named() {
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 1e-9);
+        assert!((Easing::Cubic.apply(0.5) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn smooth_curves_never_move_backwards() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::Cubic,
+        ] {
+            let samples: Vec<f64> = (0..=100).map(|i| easing.apply(i as f64 / 100.0)).collect();
+            assert!(
+                samples.windows(2).all(|pair| pair[1] >= pair[0]),
+                "{:?} is not monotonic",
+                easing
+            );
+        }
+    }
+
+    #[test]
+    fn peak_velocity_bounds_the_sampled_slope() {
+        for easing in ALL {
+            let step = 1e-4;
+            let steepest = (0..10_000)
+                .map(|i| {
+                    let t = i as f64 * step;
+                    (easing.apply(t + step) - easing.apply(t)) / step
+                })
+                .fold(0.0, f64::max);
+            assert!(
+                steepest <= easing.peak_velocity() + 1e-3,
+                "{:?}: slope {} above {}",
+                easing,
+                steepest,
+                easing.peak_velocity()
+            );
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::hardware::servo::actor::ControllerMetrics;
+use crate::hardware::servo::config::Pca9685Config;
+use crate::hardware::servo::state::now_ms;
+use serde::Serialize;
+
+// A controller with an error more recent than this is reported as degraded
+const DEGRADED_WINDOW_MS: u64 = 60_000;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerHealth {
+    #[default]
+    Healthy,
+    Degraded, // Recent errors, but writes are getting through
+    Offline,  // Writes keep failing even after retries
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct HealthStatus {
+    pub state: ControllerHealth,
+    pub errors: u64,         // Failed attempts, retried or not
+    pub retried_writes: u64, // Operations that only succeeded after a retry
+    pub failed_writes: u64,  // Operations that failed every retry
+    pub consecutive_failures: u32,
+    pub reconnects: u64, // Times the board was re-initialized
+    pub last_error: Option<String>,
+    pub last_error_ms: Option<u64>, // Unix timestamp of the last failed attempt
+}
+
+impl HealthStatus {
+    pub fn record_error(&mut self, error: &str) {
+        self.errors += 1;
+        self.last_error = Some(error.to_string());
+        self.last_error_ms = Some(now_ms());
+    }
+
+    pub fn record_success(&mut self, retries: u32) {
+        if retries > 0 {
+            self.retried_writes += 1;
+        }
+        self.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&mut self) {
+        self.failed_writes += 1;
+        self.consecutive_failures += 1;
+    }
+
+    // Snapshot with `state` worked out from the counters
+    pub fn evaluate(&self, offline_after: u32) -> Self {
+        let state = if self.consecutive_failures >= offline_after.max(1) {
+            ControllerHealth::Offline
+        } else if self
+            .last_error_ms
+            .is_some_and(|at| now_ms().saturating_sub(at) < DEGRADED_WINDOW_MS)
+        {
+            ControllerHealth::Degraded
+        } else {
+            ControllerHealth::Healthy
+        };
+
+        Self {
+            state,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct ControllerStatus {
+    #[serde(flatten)]
+    pub config: Pca9685Config,
+    pub health: HealthStatus,
+    pub metrics: ControllerMetrics,
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+
+tokio::task_local! {
+    static OWNER: Arc<Owner>;
+}
+
+// Owner names given to routines; nothing else may claim them
+pub const ROUTINE_OWNER_PREFIX: &str = "routine-";
+
+// Who a task moves servos for. Moves from inside `run_as` may drive servos leased to that owner
+pub struct Owner {
+    pub name: String,
+    on_release: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl Owner {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            on_release: None,
+        }
+    }
+
+    // Runs `on_release` once the last task acting for this owner is gone, however it ended
+    pub fn with_release(name: &str, on_release: impl FnOnce() + Send + Sync + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            on_release: Some(Box::new(on_release)),
+        }
+    }
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        if let Some(on_release) = self.on_release.take() {
+            on_release();
+        }
+    }
+}
+
+pub fn current_owner() -> Option<Arc<Owner>> {
+    OWNER.try_with(Arc::clone).ok()
+}
+
+// Runs `future` on behalf of `owner`; spawned tasks have to be wrapped again to inherit it
+pub async fn run_as<F: Future>(owner: Option<Arc<Owner>>, future: F) -> F::Output {
+    match owner {
+        Some(owner) => OWNER.scope(owner, future).await,
+        None => future.await,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    #[default]
+    Reject, // Fail straight away, naming the current owner
+    Queue,   // Wait until the servos are released
+    Preempt, // Take the servos from owners with a lower priority
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Lease {
+    pub owner: String,
+    pub priority: i32,
+    pub acquired_ms: u64, // Unix timestamp
+}
+
+#[derive(Clone, Deserialize)]
+pub struct LeaseRequest {
+    pub owner: String,
+    pub servos: Vec<String>,
+    #[serde(default)]
+    pub priority: i32, // Higher wins when preempting
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>, // Queue only: give up after this long
+}
@@ -1,5 +1,36 @@
+pub(crate) mod actor;
+pub(crate) mod calibration;
 pub(crate) mod config;
+pub(crate) mod easing;
+pub(crate) mod health;
+pub(crate) mod lease;
+pub(crate) mod motion;
 mod pca9685;
+pub mod simulated;
+pub(crate) mod state;
+pub(crate) mod trace;
 
-pub use config::Pca9685Config;
+use crate::errors::hardware_error::HardwareError;
+use crate::traits::hardware::{PwmBackend, PwmDriver};
+use std::sync::Arc;
+use trace::{TraceRecorder, TracedBackend};
+
+pub use actor::ControllerHandle;
+pub use config::{DriverKind, Pca9685Config};
+pub use easing::Easing;
 pub use pca9685::Pca9685Controller;
+pub use simulated::SimulatedPwmDriver;
+
+// Builds the backend selected by the controller's `driver` setting, with its writes going to
+// `recorder`, and starts the task that owns it
+pub fn create_driver(
+    config: Pca9685Config,
+    recorder: Arc<TraceRecorder>,
+) -> Result<Arc<dyn PwmDriver>, HardwareError> {
+    let backend: Box<dyn PwmBackend> = match config.driver {
+        DriverKind::Pca9685 => Box::new(Pca9685Controller::new(config)?),
+        DriverKind::Simulated => Box::new(SimulatedPwmDriver::new(config)?),
+    };
+    let backend = Box::new(TracedBackend::new(backend, recorder));
+    Ok(Arc::new(ControllerHandle::spawn(backend)?))
+}
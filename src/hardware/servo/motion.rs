@@ -0,0 +1,185 @@
+use crate::hardware::servo::config::ServoConfig;
+use crate::hardware::servo::easing::Easing;
+use serde::Serialize;
+use std::time::Instant;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct MoveOutcome {
+    pub requested_duration_ms: u64,
+    pub duration_ms: u64, // Duration actually used, after speed and acceleration limits
+    pub easing: Easing,
+    pub clamped: bool, // The move was slowed down to stay within the servo's limits
+}
+
+impl MoveOutcome {
+    pub fn unlimited(duration_ms: u64, easing: Easing) -> Self {
+        Self {
+            requested_duration_ms: duration_ms,
+            duration_ms,
+            easing,
+            clamped: false,
+        }
+    }
+}
+
+// Stretches a move over `distance` degrees until neither the peak speed nor the peak
+// acceleration of the eased curve exceeds the servo's limits
+pub fn limit_move(
+    config: &ServoConfig,
+    distance: f64,
+    duration_ms: u64,
+    easing: Easing,
+) -> MoveOutcome {
+    let distance = distance.abs();
+    if distance == 0.0 || (config.max_speed_deg_per_s.is_none() && config.max_accel.is_none()) {
+        return MoveOutcome::unlimited(duration_ms, easing);
+    }
+
+    // Curves that jump in velocity can never honour an acceleration limit
+    let easing = match (config.max_accel, easing.peak_acceleration()) {
+        (Some(_), None) => Easing::EaseInOut,
+        _ => easing,
+    };
+
+    let mut seconds = duration_ms as f64 / 1000.0;
+    if let Some(max_speed) = config.max_speed_deg_per_s {
+        seconds = seconds.max(distance * easing.peak_velocity() / max_speed);
+    }
+    if let (Some(max_accel), Some(peak)) = (config.max_accel, easing.peak_acceleration()) {
+        seconds = seconds.max((distance * peak / max_accel).sqrt());
+    }
+
+    let limited_ms = (seconds * 1000.0).ceil() as u64;
+    MoveOutcome {
+        requested_duration_ms: duration_ms,
+        duration_ms: limited_ms.max(duration_ms),
+        easing,
+        clamped: limited_ms > duration_ms,
+    }
+}
+
+// A value eased from `from` to `to`. The motion loop samples it against the clock, so
+// trajectories started together stay together however late a tick runs
+#[derive(Clone, Copy, Debug)]
+pub struct Sweep {
+    pub from: f64,
+    pub to: f64,
+    pub duration_ms: u64,
+    pub easing: Easing,
+    pub started: Instant,
+}
+
+impl Sweep {
+    pub fn new(from: f64, to: f64, duration_ms: u64, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration_ms,
+            easing,
+            started: Instant::now(),
+        }
+    }
+
+    // Goes straight to `value` on the next tick
+    pub fn hold(value: f64) -> Self {
+        Self::new(value, value, 0, Easing::Linear)
+    }
+
+    pub fn progress(&self, now: Instant) -> f64 {
+        if self.duration_ms == 0 {
+            return 1.0;
+        }
+        let elapsed = now.saturating_duration_since(self.started).as_secs_f64() * 1000.0;
+        (elapsed / self.duration_ms as f64).min(1.0)
+    }
+
+    pub fn value_at(&self, now: Instant) -> f64 {
+        let progress = self.progress(now);
+        if progress >= 1.0 {
+            return self.to;
+        }
+        let progress = self.easing.apply(progress);
+        self.from + (self.to - self.from) * progress
+    }
+
+    pub fn is_finished(&self, now: Instant) -> bool {
+        self.progress(now) >= 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn servo(limits: serde_json::Value) -> ServoConfig {
+        let mut config = json!({
+            "name": "Grabber Arm 1",
+            "controller_id": "body",
+            "channel": 0,
+            "min_angle": 0,
+            "max_angle": 180
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(limits.as_object().unwrap().clone());
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn servos_without_limits_keep_the_requested_duration() {
+        let outcome = limit_move(&servo(json!({})), 180.0, 100, Easing::Cubic);
+        assert_eq!(outcome.duration_ms, 100);
+        assert_eq!(outcome.easing, Easing::Cubic);
+        assert!(!outcome.clamped);
+    }
+
+    #[test]
+    fn fast_moves_are_stretched_to_the_speed_limit() {
+        let config = servo(json!({ "max_speed_deg_per_s": 90 }));
+
+        let outcome = limit_move(&config, 90.0, 100, Easing::Linear);
+        assert_eq!(outcome.requested_duration_ms, 100);
+        assert_eq!(outcome.duration_ms, 1000);
+        assert!(outcome.clamped);
+
+        // Eased curves peak at twice the average speed
+        let outcome = limit_move(&config, -90.0, 100, Easing::EaseInOut);
+        assert_eq!(outcome.duration_ms, 2000);
+    }
+
+    #[test]
+    fn slow_moves_are_left_alone() {
+        let config = servo(json!({ "max_speed_deg_per_s": 90 }));
+        let outcome = limit_move(&config, 45.0, 2000, Easing::Linear);
+        assert_eq!(outcome.duration_ms, 2000);
+        assert!(!outcome.clamped);
+    }
+
+    #[test]
+    fn fast_moves_are_stretched_to_the_acceleration_limit() {
+        let config = servo(json!({ "max_accel": 360 }));
+        let outcome = limit_move(&config, 90.0, 0, Easing::EaseInOut);
+        assert_eq!(outcome.duration_ms, 1000);
+        assert!(outcome.clamped);
+    }
+
+    #[test]
+    fn an_acceleration_limit_replaces_curves_that_jump() {
+        let config = servo(json!({ "max_accel": 360 }));
+        for easing in [Easing::Linear, Easing::Bounce] {
+            let outcome = limit_move(&config, 90.0, 5000, easing);
+            assert_eq!(outcome.easing, Easing::EaseInOut);
+            assert_eq!(outcome.duration_ms, 5000);
+        }
+    }
+
+    #[test]
+    fn zero_distance_moves_are_never_limited() {
+        let config = servo(json!({ "max_speed_deg_per_s": 1, "max_accel": 1 }));
+        let outcome = limit_move(&config, 0.0, 0, Easing::Linear);
+        assert_eq!(outcome.duration_ms, 0);
+        assert_eq!(outcome.easing, Easing::Linear);
+    }
+}
@@ -1,73 +1,398 @@
 use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::health::HealthStatus;
 use crate::hardware::servo::Pca9685Config;
+use crate::traits::hardware::PwmBackend;
+use async_trait::async_trait;
+use linux_embedded_hal::i2cdev::core::I2CDevice;
+use linux_embedded_hal::i2cdev::linux::LinuxI2CDevice;
 use linux_embedded_hal::I2cdev;
-use pwm_pca9685::{Address, Channel, Pca9685};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use log::{info, warn};
+use pwm_pca9685::{Address, Channel, ChannelOnOffControl, Pca9685};
+use std::fmt::Display;
+use std::time::Duration;
+
+// MODE1 comes out of reset with SLEEP set, and it stays clear once the board is running
+const MODE1_REGISTER: u8 = 0x00;
+const MODE1_SLEEP: u8 = 0x10;
+// Register auto-increment, so a block of channels goes out in one transaction
+const MODE1_AUTO_INCREMENT: u8 = 0x20;
+// LED0_ON_L; each channel has ON_L, ON_H, OFF_L, OFF_H after it
+const LED0_REGISTER: u8 = 0x06;
+// Full on/off flag in the ON_H and OFF_H registers
+const FULL_FLAG: u8 = 0x10;
+
+type Pwm = Pca9685<I2cdev>;
+
+// The driver, plus a raw handle for the block writes it has no API for
+struct Link {
+    pwm: Pwm,
+    bus: LinuxI2CDevice,
+}
 
 pub struct Pca9685Controller {
     config: Pca9685Config,
-    device: Arc<Mutex<Pca9685<I2cdev>>>,
+    frequency: f64,
+    device: Device,
+    health: HealthStatus,
+}
+
+struct Device {
+    link: Option<Link>, // Dropped after a failed write so the next attempt starts from a fresh init
+    // Registers last written to each channel, so a batch can fill the gaps in the block it
+    // writes and a re-initialized board gets its outputs back
+    channels: [ChannelOnOffControl; 16],
 }
 
 impl Pca9685Controller {
     pub fn new(config: Pca9685Config) -> Result<Self, HardwareError> {
-        let i2c = I2cdev::new("/dev/i2c-1").map_err(|e| {
-            HardwareError::InitializationError(format!("Failed to open I2C device: {:?}", e))
+        let frequency = config.actual_frequency()?;
+        // Start from what the board is already driving, which may have been set before this
+        // process started, so the first batch doesn't switch those channels off
+        let channels = read_channels(&config).map_err(|e| {
+            HardwareError::InitializationError(format!(
+                "Failed to read channels of controller '{}': {}",
+                config.id, e
+            ))
         })?;
+        let link = open(&config)?;
 
-        let address = if config.i2c_address == "default" {
-            Address::default()
-        } else {
-            let addr = u8::from_str_radix(&config.i2c_address.trim_start_matches("0x"), 16)
-                .map_err(|_| {
-                    HardwareError::InvalidParameter(format!(
-                        "Invalid I2C address: {}",
-                        config.i2c_address
-                    ))
-                })?;
-            Address::from(addr)
-        };
+        Ok(Self {
+            config,
+            frequency,
+            device: Device {
+                link: Some(link),
+                channels,
+            },
+            health: HealthStatus::default(),
+        })
+    }
 
-        let mut device = Pca9685::new(i2c, address).map_err(|e| {
-            HardwareError::InitializationError(format!("Failed to initialize PCA9685: {}", e))
-        })?;
+    // Runs `op` on the board, re-initializing it and backing off between failed attempts
+    async fn with_retry<T, E: Display>(
+        &mut self,
+        action: &str,
+        mut op: impl FnMut(&mut Link) -> Result<T, E>,
+    ) -> Result<T, HardwareError> {
+        let retry = self.config.retry.clone();
+        let mut attempt = 0;
 
-        // Set frequency (usually 50Hz for servos)
-        device.set_prescale(100).map_err(|e| {
-            HardwareError::InitializationError(format!("Failed to set frequency: {}", e))
-        })?;
+        loop {
+            let result = self
+                .connect()
+                .and_then(|link| op(link).map_err(|e| format!("Failed to {}: {}", action, e)));
 
-        // Enable the device
-        device.enable().map_err(|e| {
-            HardwareError::InitializationError(format!("Failed to enable device: {}", e))
-        })?;
+            let error = match result {
+                Ok(value) => {
+                    self.health.record_success(attempt);
+                    return Ok(value);
+                }
+                Err(error) => error,
+            };
 
-        Ok(Self {
-            config,
-            device: Arc::new(Mutex::new(device)),
+            warn!(
+                "Controller '{}': {} (attempt {} of {})",
+                self.config.id,
+                error,
+                attempt + 1,
+                retry.attempts + 1
+            );
+            self.health.record_error(&error);
+            // A board that browned out has lost its prescale and channels
+            self.device.link = None;
+
+            if attempt >= retry.attempts {
+                self.health.record_failure();
+                return Err(HardwareError::CommunicationError(error));
+            }
+            attempt += 1;
+            tokio::time::sleep(Duration::from_millis(retry.backoff(attempt))).await;
+        }
+    }
+
+    // The open board, re-initialized with the last known channel values if it was dropped
+    fn connect(&mut self) -> Result<&mut Link, String> {
+        if let Some(link) = self.device.link.take() {
+            return Ok(self.device.link.insert(link));
+        }
+
+        let mut link = open(&self.config).map_err(|e| e.to_string())?;
+        link.pwm
+            .set_all_channels(&self.device.channels)
+            .map_err(|e| format!("Failed to restore channels: {}", e))?;
+
+        info!(
+            "Controller '{}' re-initialized and channels restored",
+            self.config.id
+        );
+        self.health.reconnects += 1;
+        Ok(self.device.link.insert(link))
+    }
+}
+
+#[async_trait]
+impl PwmBackend for Pca9685Controller {
+    fn get_config(&self) -> &Pca9685Config {
+        &self.config
+    }
+
+    fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    fn health(&self) -> HealthStatus {
+        self.health.evaluate(self.config.retry.offline_after)
+    }
+
+    async fn check_health(&mut self) -> Result<(), HardwareError> {
+        if self.device.link.is_some() {
+            match read_mode1(&self.config) {
+                Ok(mode1) if mode1 & MODE1_SLEEP == 0 => return Ok(()),
+                Ok(_) => warn!("Controller '{}' was reset, re-initializing", self.config.id),
+                Err(e) => {
+                    warn!("Controller '{}' health check failed: {}", self.config.id, e);
+                    self.health.record_error(&e);
+                }
+            }
+            self.device.link = None;
+        }
+
+        // Nothing to write; connecting is the whole job
+        self.with_retry("re-initialize", |_| Ok::<(), String>(()))
+            .await
+    }
+
+    async fn set_pulse(&mut self, channel: u8, pulse_width: u16) -> Result<(), HardwareError> {
+        let index = channel as usize;
+        let channel = to_channel(channel)?;
+
+        self.with_retry("set channel", |link| {
+            link.pwm.set_channel_on(channel, 0)?;
+            link.pwm.set_channel_off(channel, pulse_width)
         })
+        .await?;
+
+        self.device.channels[index] = pulse_control(pulse_width);
+        Ok(())
     }
 
-    pub async fn move_servo(
-        &self,
-        channel: Channel,
-        pulse_width: u16,
-    ) -> Result<(), HardwareError> {
-        let mut device = self.device.lock().await;
+    async fn set_pulses(&mut self, pulses: &[(u8, u16)]) -> Result<(), HardwareError> {
+        let mut channels = self.device.channels;
+        for &(channel, pulse_width) in pulses {
+            to_channel(channel)?;
+            if pulse_width > 4095 {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Pulse width {} is outside the 12-bit range",
+                    pulse_width
+                )));
+            }
+            channels[channel as usize] = pulse_control(pulse_width);
+        }
+        let channel_numbers = pulses.iter().map(|&(channel, _)| channel);
+        let (first, last) = match (channel_numbers.clone().min(), channel_numbers.max()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(()),
+        };
+
+        // One auto-increment write from the lowest to the highest channel, so every channel
+        // changes on the same PWM cycle; channels in between get their last values again
+        let block = encode_block(&channels, first, last);
+        self.with_retry("set channels", |link| link.bus.write(&block))
+            .await?;
 
-        device.set_channel_on(channel, 0).map_err(|e| {
-            HardwareError::CommunicationError(format!("Failed to set channel on: {}", e))
-        })?;
+        self.device.channels = channels;
+        Ok(())
+    }
 
-        device.set_channel_off(channel, pulse_width).map_err(|e| {
-            HardwareError::CommunicationError(format!("Failed to set channel off: {}", e))
-        })?;
+    async fn set_full_off(&mut self, channel: u8) -> Result<(), HardwareError> {
+        let index = channel as usize;
+        let channel = to_channel(channel)?;
 
+        self.with_retry("set channel full off", |link| {
+            link.pwm.set_channel_full_off(channel)
+        })
+        .await?;
+
+        self.device.channels[index].full_off = true;
         Ok(())
     }
 
-    pub fn get_config(&self) -> &Pca9685Config {
-        &self.config
+    async fn set_full_on(&mut self, channel: u8) -> Result<(), HardwareError> {
+        let index = channel as usize;
+        let channel = to_channel(channel)?;
+
+        // Full off wins over full on, so clear it from the OFF register as well
+        self.with_retry("set channel full on", |link| {
+            link.pwm.set_channel_full_on(channel, 0)?;
+            link.pwm.set_channel_off(channel, 0)
+        })
+        .await?;
+
+        self.device.channels[index] = ChannelOnOffControl {
+            on: 0,
+            off: 0,
+            full_on: true,
+            full_off: false,
+        };
+        Ok(())
+    }
+}
+
+// Opens the board and brings it up at the configured frequency
+fn open(config: &Pca9685Config) -> Result<Link, HardwareError> {
+    let prescale = config.prescale()?;
+    let frequency = config.actual_frequency()?;
+
+    let bus = config.bus_path();
+    let i2c = I2cdev::new(&bus).map_err(|e| {
+        HardwareError::InitializationError(format!("Failed to open I2C device {}: {:?}", bus, e))
+    })?;
+
+    let address = Address::from(config.address()?);
+
+    let mut device = Pca9685::new(i2c, address).map_err(|e| {
+        HardwareError::InitializationError(format!("Failed to initialize PCA9685: {}", e))
+    })?;
+
+    // Set frequency (usually 50Hz for servos)
+    info!(
+        "Controller '{}' on {} prescale {} ({:.2} Hz requested, {:.2} Hz actual)",
+        config.id, bus, prescale, config.frequency, frequency
+    );
+    device.set_prescale(prescale).map_err(|e| {
+        HardwareError::InitializationError(format!("Failed to set frequency: {}", e))
+    })?;
+
+    // Enable the device
+    device.enable().map_err(|e| {
+        HardwareError::InitializationError(format!("Failed to enable device: {}", e))
+    })?;
+
+    // The driver only turns auto-increment on before its own multi-byte writes, and never
+    // clears it again, so setting it here covers the block writes that bypass it
+    let mut bus = LinuxI2CDevice::new(&bus, config.address()? as u16).map_err(|e| {
+        HardwareError::InitializationError(format!("Failed to open I2C device {}: {}", bus, e))
+    })?;
+    let mode1 = bus
+        .smbus_read_byte_data(MODE1_REGISTER)
+        .and_then(|mode1| bus.smbus_write_byte_data(MODE1_REGISTER, mode1 | MODE1_AUTO_INCREMENT));
+    mode1.map_err(|e| {
+        HardwareError::InitializationError(format!("Failed to enable auto-increment: {}", e))
+    })?;
+
+    Ok(Link { pwm: device, bus })
+}
+
+// Reads MODE1 over a separate handle, since the driver only ever writes
+fn read_mode1(config: &Pca9685Config) -> Result<u8, String> {
+    let address = config.address().map_err(|e| e.to_string())?;
+    let mut device = LinuxI2CDevice::new(config.bus_path(), address as u16)
+        .map_err(|e| format!("Failed to open I2C device: {}", e))?;
+    device
+        .smbus_read_byte_data(MODE1_REGISTER)
+        .map_err(|e| format!("Failed to read MODE1: {}", e))
+}
+
+// Reads every channel's ON/OFF registers, one byte at a time since auto-increment may be off
+fn read_channels(config: &Pca9685Config) -> Result<[ChannelOnOffControl; 16], String> {
+    let address = config.address().map_err(|e| e.to_string())?;
+    let mut device = LinuxI2CDevice::new(config.bus_path(), address as u16)
+        .map_err(|e| format!("Failed to open I2C device: {}", e))?;
+
+    let mut channels = [ChannelOnOffControl::default(); 16];
+    for (index, channel) in channels.iter_mut().enumerate() {
+        let mut registers = [0u8; 4];
+        for (offset, register) in registers.iter_mut().enumerate() {
+            let address = LED0_REGISTER + (index * 4 + offset) as u8;
+            *register = device
+                .smbus_read_byte_data(address)
+                .map_err(|e| format!("Failed to read register 0x{:02X}: {}", address, e))?;
+        }
+        let [on_l, on_h, off_l, off_h] = registers;
+
+        *channel = ChannelOnOffControl {
+            on: u16::from_le_bytes([on_l, on_h & 0x0F]),
+            off: u16::from_le_bytes([off_l, off_h & 0x0F]),
+            full_on: on_h & FULL_FLAG != 0,
+            full_off: off_h & FULL_FLAG != 0,
+        };
+    }
+    Ok(channels)
+}
+
+// LEDn_ON_L of `first` followed by the ON/OFF registers of every channel up to `last`
+fn encode_block(channels: &[ChannelOnOffControl; 16], first: u8, last: u8) -> Vec<u8> {
+    let mut block = vec![LED0_REGISTER + first * 4];
+    for channel in &channels[first as usize..=last as usize] {
+        let [on_l, on_h] = channel.on.to_le_bytes();
+        let [off_l, off_h] = channel.off.to_le_bytes();
+        block.extend([
+            on_l,
+            on_h | if channel.full_on { FULL_FLAG } else { 0 },
+            off_l,
+            off_h | if channel.full_off { FULL_FLAG } else { 0 },
+        ]);
+    }
+    block
+}
+
+fn pulse_control(pulse_width: u16) -> ChannelOnOffControl {
+    ChannelOnOffControl {
+        on: 0,
+        off: pulse_width,
+        full_on: false,
+        full_off: false,
+    }
+}
+
+fn to_channel(channel: u8) -> Result<Channel, HardwareError> {
+    match channel {
+        0 => Ok(Channel::C0),
+        1 => Ok(Channel::C1),
+        2 => Ok(Channel::C2),
+        3 => Ok(Channel::C3),
+        4 => Ok(Channel::C4),
+        5 => Ok(Channel::C5),
+        6 => Ok(Channel::C6),
+        7 => Ok(Channel::C7),
+        8 => Ok(Channel::C8),
+        9 => Ok(Channel::C9),
+        10 => Ok(Channel::C10),
+        11 => Ok(Channel::C11),
+        12 => Ok(Channel::C12),
+        13 => Ok(Channel::C13),
+        14 => Ok(Channel::C14),
+        15 => Ok(Channel::C15),
+        _ => Err(HardwareError::InvalidParameter(format!(
+            "Invalid channel number: {}",
+            channel
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_cover_only_the_affected_channels() {
+        let mut channels = [ChannelOnOffControl::default(); 16];
+        channels[2] = pulse_control(0x123);
+        channels[3].full_off = true;
+        channels[4] = pulse_control(0x456);
+
+        let block = encode_block(&channels, 2, 4);
+        assert_eq!(block[0], 0x0E); // LED2_ON_L
+        assert_eq!(
+            block[1..],
+            [0, 0, 0x23, 0x01, 0, 0, 0, FULL_FLAG, 0, 0, 0x56, 0x04]
+        );
+    }
+
+    #[test]
+    fn full_on_sets_the_flag_in_on_h() {
+        let mut channels = [ChannelOnOffControl::default(); 16];
+        channels[15].full_on = true;
+
+        assert_eq!(encode_block(&channels, 15, 15), [0x42, 0, FULL_FLAG, 0, 0]);
     }
 }
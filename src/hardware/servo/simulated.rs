@@ -0,0 +1,233 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::config::PWM_RESOLUTION;
+use crate::hardware::servo::health::HealthStatus;
+use crate::hardware::servo::state::now_ms;
+use crate::hardware::servo::Pca9685Config;
+use crate::traits::hardware::PwmBackend;
+use async_trait::async_trait;
+use log::debug;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Oldest writes are dropped past this point so a long session can't grow without bound
+const MAX_RECORDED_WRITES: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ChannelWrite {
+    pub channel: u8,
+    pub pulse_width: Option<u16>, // None when turned fully off, 4096 when fully on
+    pub timestamp_ms: u64,
+}
+
+#[derive(Default)]
+struct BoardState {
+    channels: [Option<u16>; 16], // None when fully off, 4096 when fully on
+    writes: VecDeque<ChannelWrite>,
+}
+
+// What a simulated board has been sent. Cloned out before the driver is handed to its
+// controller task, so tests and the API can still look at it afterwards
+#[derive(Clone, Default)]
+pub struct SimulatedBoard {
+    state: Arc<Mutex<BoardState>>,
+}
+
+impl SimulatedBoard {
+    pub fn channels(&self) -> [Option<u16>; 16] {
+        self.state.lock().unwrap().channels
+    }
+
+    pub fn writes(&self) -> Vec<ChannelWrite> {
+        self.state.lock().unwrap().writes.iter().cloned().collect()
+    }
+}
+
+// Stands in for a PCA9685 so the API can run without I2C hardware
+pub struct SimulatedPwmDriver {
+    config: Pca9685Config,
+    frequency: f64,
+    board: SimulatedBoard,
+}
+
+impl SimulatedPwmDriver {
+    pub fn new(config: Pca9685Config) -> Result<Self, HardwareError> {
+        let frequency = config.actual_frequency()?;
+
+        Ok(Self {
+            config,
+            frequency,
+            board: SimulatedBoard::default(),
+        })
+    }
+
+    pub fn board(&self) -> SimulatedBoard {
+        self.board.clone()
+    }
+
+    fn write(&mut self, channel: u8, pulse_width: Option<u16>) -> Result<(), HardwareError> {
+        let mut state = self.board.state.lock().unwrap();
+        let slot = state.channels.get_mut(channel as usize).ok_or_else(|| {
+            HardwareError::InvalidParameter(format!("Invalid channel number: {}", channel))
+        })?;
+        *slot = pulse_width;
+
+        if state.writes.len() == MAX_RECORDED_WRITES {
+            state.writes.pop_front();
+        }
+        state.writes.push_back(ChannelWrite {
+            channel,
+            pulse_width,
+            timestamp_ms: now_ms(),
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PwmBackend for SimulatedPwmDriver {
+    fn get_config(&self) -> &Pca9685Config {
+        &self.config
+    }
+
+    fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    // Simulated writes never fail
+    fn health(&self) -> HealthStatus {
+        HealthStatus::default()
+    }
+
+    fn simulated(&self) -> Option<SimulatedBoard> {
+        Some(self.board())
+    }
+
+    async fn check_health(&mut self) -> Result<(), HardwareError> {
+        Ok(())
+    }
+
+    async fn set_pulse(&mut self, channel: u8, pulse_width: u16) -> Result<(), HardwareError> {
+        debug!(
+            "Simulated controller '{}' channel {} pulse width {}",
+            self.config.id, channel, pulse_width
+        );
+        self.write(channel, Some(pulse_width))
+    }
+
+    async fn set_pulses(&mut self, pulses: &[(u8, u16)]) -> Result<(), HardwareError> {
+        debug!(
+            "Simulated controller '{}' batch of {} channels",
+            self.config.id,
+            pulses.len()
+        );
+
+        // Check the whole batch first, the real chip writes all or nothing
+        if let Some((channel, _)) = pulses.iter().find(|(channel, _)| *channel > 15) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Invalid channel number: {}",
+                channel
+            )));
+        }
+        for &(channel, pulse_width) in pulses {
+            self.write(channel, Some(pulse_width))?;
+        }
+        Ok(())
+    }
+
+    async fn set_full_off(&mut self, channel: u8) -> Result<(), HardwareError> {
+        debug!(
+            "Simulated controller '{}' channel {} full off",
+            self.config.id, channel
+        );
+        self.write(channel, None)
+    }
+
+    async fn set_full_on(&mut self, channel: u8) -> Result<(), HardwareError> {
+        debug!(
+            "Simulated controller '{}' channel {} full on",
+            self.config.id, channel
+        );
+        self.write(channel, Some(PWM_RESOLUTION as u16))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::servo::ControllerHandle;
+    use crate::traits::hardware::PwmDriver;
+
+    fn config(id: &str) -> Pca9685Config {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "i2c_address": "0x40",
+            "frequency": 50,
+            "driver": "simulated"
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn records_every_write_in_order() {
+        let mut driver = SimulatedPwmDriver::new(config("dome")).unwrap();
+        let board = driver.board();
+
+        driver.set_pulse(0, 300).await.unwrap();
+        driver.set_full_on(1).await.unwrap();
+        driver.set_full_off(0).await.unwrap();
+
+        let writes: Vec<(u8, Option<u16>)> = board
+            .writes()
+            .iter()
+            .map(|write| (write.channel, write.pulse_width))
+            .collect();
+        assert_eq!(writes, vec![(0, Some(300)), (1, Some(4096)), (0, None)]);
+
+        let channels = board.channels();
+        assert_eq!(channels[0], None);
+        assert_eq!(channels[1], Some(4096));
+    }
+
+    #[tokio::test]
+    async fn a_batch_with_a_bad_channel_writes_nothing() {
+        let mut driver = SimulatedPwmDriver::new(config("dome")).unwrap();
+        let board = driver.board();
+
+        assert!(driver.set_pulses(&[(0, 300), (16, 300)]).await.is_err());
+        assert!(board.writes().is_empty());
+
+        driver.set_pulses(&[(0, 300), (3, 400)]).await.unwrap();
+        assert_eq!(board.channels()[..4], [Some(300), None, None, Some(400)]);
+    }
+
+    #[tokio::test]
+    async fn the_write_log_is_capped() {
+        let mut driver = SimulatedPwmDriver::new(config("dome")).unwrap();
+        let board = driver.board();
+
+        for i in 0..MAX_RECORDED_WRITES + 5 {
+            driver.set_pulse(0, (i % 4096) as u16).await.unwrap();
+        }
+        let writes = board.writes();
+        assert_eq!(writes.len(), MAX_RECORDED_WRITES);
+        assert_eq!(writes[0].pulse_width, Some(5));
+    }
+
+    #[tokio::test]
+    async fn the_board_stays_readable_behind_its_controller_task() {
+        let driver = SimulatedPwmDriver::new(config("dome")).unwrap();
+        let handle = ControllerHandle::spawn(Box::new(driver)).unwrap();
+        let board = handle.simulated().unwrap();
+
+        handle.set_pulses(&[(2, 250), (5, 350)]).await.unwrap();
+        handle.set_full_on(15).await.unwrap();
+
+        let channels = board.channels();
+        assert_eq!(channels[2], Some(250));
+        assert_eq!(channels[5], Some(350));
+        assert_eq!(channels[15], Some(4096));
+        assert_eq!(board.writes().len(), 3);
+    }
+}
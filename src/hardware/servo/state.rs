@@ -0,0 +1,66 @@
+use crate::hardware::servo::config::ServoConfig;
+use crate::hardware::servo::lease::Lease;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Serialize)]
+pub struct ServoState {
+    pub angle: Option<f64>,  // Last angle written to the servo
+    pub pulse: Option<u16>,  // Last pulse width written, in ticks
+    pub target: Option<f64>, // Angle of the current or most recent move
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>, // Continuous servos only, -1.0..=1.0
+    pub moving: bool,
+    pub relaxed: bool,             // Channel is not being driven
+    pub last_move_ms: Option<u64>, // Unix timestamp of the last write
+    pub last_error: Option<String>,
+    #[serde(skip)]
+    pub(crate) generation: u64, // Bumped on every new move so a running sweep knows it was superseded
+}
+
+impl Default for ServoState {
+    fn default() -> Self {
+        // Channels are not driven until the first move after startup
+        Self {
+            angle: None,
+            pulse: None,
+            target: None,
+            speed: None,
+            moving: false,
+            relaxed: true,
+            last_move_ms: None,
+            last_error: None,
+            generation: 0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct ServoStatus {
+    #[serde(flatten)]
+    pub config: ServoConfig,
+    pub state: ServoState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease: Option<Lease>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EstopMode {
+    #[default]
+    Relax, // Turn every channel fully off
+    Freeze, // Hold every servo where it is
+}
+
+#[derive(Clone, Serialize)]
+pub struct EmergencyStop {
+    pub mode: EstopMode,
+    pub engaged_ms: u64, // Unix timestamp the stop was engaged at
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,631 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::config::PWM_RESOLUTION;
+use crate::hardware::servo::health::HealthStatus;
+use crate::hardware::servo::simulated::SimulatedBoard;
+use crate::hardware::servo::state::now_ms;
+use crate::hardware::servo::Pca9685Config;
+use crate::traits::hardware::PwmBackend;
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{LineWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// LED0_ON_L; every channel has four registers from here on, ON_L, ON_H, OFF_L and OFF_H
+const LED0_ON_L: u8 = 0x06;
+// Mismatches past this many are counted but not listed
+const MAX_REPORTED_MISMATCHES: usize = 50;
+
+// One register write as it reached the board. Stored one per line in JSONL trace files
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TraceEntry {
+    pub timestamp_ms: u64, // Since the recording started
+    #[serde(default)]
+    pub unix_ms: u64, // Wall clock time of the write, to line a trace up with the logs
+    pub controller: String,
+    pub address: String,
+    pub channel: u8,
+    pub register: String, // First register of the transaction that carried the write
+    pub value: Option<u16>, // Pulse width, None when turned fully off, 4096 when fully on
+}
+
+struct Recording {
+    id: u64,
+    started: Instant,
+    writes: u64,
+    target: Target,
+}
+
+enum Target {
+    File {
+        path: String,
+        writer: LineWriter<File>, // Flushed per entry, so a crash doesn't lose the tail
+    },
+    Memory(Vec<TraceEntry>),
+}
+
+#[derive(Clone, Serialize)]
+pub struct TraceStatus {
+    pub path: String,
+    pub writes: u64,
+    pub elapsed_ms: u64,
+}
+
+// Taps every board write while at least one recording is running
+pub struct TraceRecorder {
+    directory: PathBuf, // Trace files are only ever read and written in here
+    recordings: Mutex<Vec<Recording>>,
+    next_id: AtomicU64,
+}
+
+impl TraceRecorder {
+    pub fn new(directory: &str) -> Self {
+        Self {
+            directory: PathBuf::from(directory),
+            recordings: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    // `path` comes from API callers, so it has to stay inside the trace directory
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, HardwareError> {
+        let relative = Path::new(path);
+        let inside = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if path.is_empty() || !inside {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Trace path '{}' must be a relative path inside the trace directory",
+                path
+            )));
+        }
+        Ok(self.directory.join(relative))
+    }
+
+    // Opens `path` in the trace directory for writing, creating any folders on the way
+    fn create(&self, path: &str) -> Result<File, HardwareError> {
+        let full_path = self.resolve(path)?;
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(File::create(full_path)?)
+    }
+
+    pub fn start_file(&self, path: &str) -> Result<(), HardwareError> {
+        let mut recordings = self.recordings.lock().unwrap();
+        if let Some(status) = file_status(&recordings) {
+            return Err(HardwareError::InvalidState(format!(
+                "Already recording a trace to '{}'",
+                status.path
+            )));
+        }
+
+        let file = self.create(path)?;
+        info!("Recording I2C trace to '{}'", path);
+        recordings.push(Recording {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            started: Instant::now(),
+            writes: 0,
+            target: Target::File {
+                path: path.to_string(),
+                writer: LineWriter::new(file),
+            },
+        });
+        Ok(())
+    }
+
+    pub fn stop_file(&self) -> Result<TraceStatus, HardwareError> {
+        let mut recordings = self.recordings.lock().unwrap();
+        let status = file_status(&recordings)
+            .ok_or_else(|| HardwareError::InvalidState("No trace is being recorded".to_string()))?;
+
+        recordings.retain(|recording| !matches!(recording.target, Target::File { .. }));
+        info!(
+            "Stopped recording I2C trace to '{}' after {} writes",
+            status.path, status.writes
+        );
+        Ok(status)
+    }
+
+    pub fn file_status(&self) -> Option<TraceStatus> {
+        file_status(&self.recordings.lock().unwrap())
+    }
+
+    // Starts collecting writes in memory, alongside any file recording
+    pub fn start_capture(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.recordings.lock().unwrap().push(Recording {
+            id,
+            started: Instant::now(),
+            writes: 0,
+            target: Target::Memory(Vec::new()),
+        });
+        id
+    }
+
+    pub fn finish_capture(&self, id: u64) -> Vec<TraceEntry> {
+        let mut recordings = self.recordings.lock().unwrap();
+        let Some(index) = recordings.iter().position(|recording| recording.id == id) else {
+            return Vec::new();
+        };
+        match recordings.remove(index).target {
+            Target::Memory(entries) => entries,
+            Target::File { .. } => Vec::new(),
+        }
+    }
+
+    pub fn read_trace(&self, path: &str) -> Result<Vec<TraceEntry>, HardwareError> {
+        fs::read_to_string(self.resolve(path)?)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    HardwareError::InvalidParameter(format!(
+                        "Invalid trace entry on line {} of '{}': {}",
+                        index + 1,
+                        path,
+                        e
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    pub fn write_trace(&self, path: &str, entries: &[TraceEntry]) -> Result<(), HardwareError> {
+        let mut writer = LineWriter::new(self.create(path)?);
+        for entry in entries {
+            write_entry(&mut writer, entry)?;
+        }
+        Ok(())
+    }
+
+    fn record(&self, config: &Pca9685Config, channel: u8, register: u8, value: Option<u16>) {
+        let mut recordings = self.recordings.lock().unwrap();
+        if recordings.is_empty() {
+            return;
+        }
+
+        let address = config
+            .address()
+            .map(|address| format!("0x{:02x}", address))
+            .unwrap_or_else(|_| config.i2c_address.clone());
+        let register = format!("0x{:02x}", register);
+        let unix_ms = now_ms();
+
+        for recording in recordings.iter_mut() {
+            let entry = TraceEntry {
+                timestamp_ms: recording.started.elapsed().as_millis() as u64,
+                unix_ms,
+                controller: config.id.clone(),
+                address: address.clone(),
+                channel,
+                register: register.clone(),
+                value,
+            };
+            recording.writes += 1;
+
+            match &mut recording.target {
+                Target::Memory(entries) => entries.push(entry),
+                Target::File { path, writer } => {
+                    if let Err(e) = write_entry(writer, &entry) {
+                        warn!("Failed to write I2C trace to '{}': {}", path, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn file_status(recordings: &[Recording]) -> Option<TraceStatus> {
+    recordings
+        .iter()
+        .find_map(|recording| match &recording.target {
+            Target::File { path, .. } => Some(TraceStatus {
+                path: path.clone(),
+                writes: recording.writes,
+                elapsed_ms: recording.started.elapsed().as_millis() as u64,
+            }),
+            Target::Memory(_) => None,
+        })
+}
+
+fn write_entry(writer: &mut impl Write, entry: &TraceEntry) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, entry)?;
+    writer.write_all(b"\n")
+}
+
+fn on_register(channel: u8) -> u8 {
+    LED0_ON_L + 4 * channel
+}
+
+// Wraps a board so every write that gets through is recorded
+pub struct TracedBackend {
+    inner: Box<dyn PwmBackend>,
+    recorder: Arc<TraceRecorder>,
+}
+
+impl TracedBackend {
+    pub fn new(inner: Box<dyn PwmBackend>, recorder: Arc<TraceRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+#[async_trait]
+impl PwmBackend for TracedBackend {
+    fn get_config(&self) -> &Pca9685Config {
+        self.inner.get_config()
+    }
+
+    fn frequency(&self) -> f64 {
+        self.inner.frequency()
+    }
+
+    fn health(&self) -> HealthStatus {
+        self.inner.health()
+    }
+
+    fn simulated(&self) -> Option<SimulatedBoard> {
+        self.inner.simulated()
+    }
+
+    async fn check_health(&mut self) -> Result<(), HardwareError> {
+        self.inner.check_health().await
+    }
+
+    async fn set_pulse(&mut self, channel: u8, pulse_width: u16) -> Result<(), HardwareError> {
+        self.inner.set_pulse(channel, pulse_width).await?;
+        self.recorder.record(
+            self.inner.get_config(),
+            channel,
+            on_register(channel),
+            Some(pulse_width),
+        );
+        Ok(())
+    }
+
+    // The whole batch goes out in one auto-increment write from the lowest channel's registers
+    async fn set_pulses(&mut self, pulses: &[(u8, u16)]) -> Result<(), HardwareError> {
+        self.inner.set_pulses(pulses).await?;
+        let first = pulses
+            .iter()
+            .map(|&(channel, _)| channel)
+            .min()
+            .unwrap_or(0);
+        for &(channel, pulse_width) in pulses {
+            self.recorder.record(
+                self.inner.get_config(),
+                channel,
+                on_register(first),
+                Some(pulse_width),
+            );
+        }
+        Ok(())
+    }
+
+    // Fully off only takes the OFF registers
+    async fn set_full_off(&mut self, channel: u8) -> Result<(), HardwareError> {
+        self.inner.set_full_off(channel).await?;
+        self.recorder.record(
+            self.inner.get_config(),
+            channel,
+            on_register(channel) + 2,
+            None,
+        );
+        Ok(())
+    }
+
+    async fn set_full_on(&mut self, channel: u8) -> Result<(), HardwareError> {
+        self.inner.set_full_on(channel).await?;
+        self.recorder.record(
+            self.inner.get_config(),
+            channel,
+            on_register(channel),
+            Some(PWM_RESOLUTION as u16),
+        );
+        Ok(())
+    }
+}
+
+// How far a trace may drift from its golden copy and still match
+#[derive(Clone, Copy, Deserialize)]
+pub struct TraceTolerance {
+    #[serde(default = "default_time_tolerance_ms")]
+    pub time_ms: u64, // Motion ticks don't land on the same millisecond twice
+    #[serde(default = "default_pulse_tolerance")]
+    pub pulse: u16,
+}
+
+impl Default for TraceTolerance {
+    fn default() -> Self {
+        Self {
+            time_ms: default_time_tolerance_ms(),
+            pulse: default_pulse_tolerance(),
+        }
+    }
+}
+
+// Two ticks at the default motion rate
+fn default_time_tolerance_ms() -> u64 {
+    40
+}
+
+fn default_pulse_tolerance() -> u16 {
+    2
+}
+
+#[derive(Serialize)]
+pub struct TraceMismatch {
+    pub timestamp_ms: u64,
+    pub controller: String,
+    pub channel: u8,
+    pub expected: Option<u16>, // None when fully off or not written yet
+    pub actual: Option<u16>,
+}
+
+#[derive(Serialize)]
+pub struct TraceReport {
+    pub matches: bool,
+    pub golden_writes: usize,
+    pub actual_writes: usize,
+    pub mismatch_count: usize,
+    pub mismatches: Vec<TraceMismatch>, // The earliest ones
+}
+
+type ChannelTrace = Vec<(u64, Option<u16>)>;
+
+// Checks each write of either trace against what the other held on that channel around the
+// same time, so writes that only one side made (a twitch, a missing step) show up too
+pub fn compare_traces(
+    golden: &[TraceEntry],
+    actual: &[TraceEntry],
+    tolerance: TraceTolerance,
+) -> TraceReport {
+    let golden_channels = by_channel(golden);
+    let actual_channels = by_channel(actual);
+    let keys: BTreeSet<&(String, u8)> = golden_channels
+        .keys()
+        .chain(actual_channels.keys())
+        .collect();
+
+    let empty = Vec::new();
+    let mut mismatches = Vec::new();
+    for key in keys {
+        let expected = golden_channels.get(key).unwrap_or(&empty);
+        let observed = actual_channels.get(key).unwrap_or(&empty);
+        let mismatch = |timestamp_ms, expected, actual| TraceMismatch {
+            timestamp_ms,
+            controller: key.0.clone(),
+            channel: key.1,
+            expected,
+            actual,
+        };
+
+        for &(at, value) in expected {
+            if !holds_near(observed, at, value, tolerance) {
+                mismatches.push(mismatch(at, value, value_at(observed, at).flatten()));
+            }
+        }
+        for &(at, value) in observed {
+            if !holds_near(expected, at, value, tolerance) {
+                mismatches.push(mismatch(at, value_at(expected, at).flatten(), value));
+            }
+        }
+    }
+
+    mismatches.sort_by_key(|mismatch| mismatch.timestamp_ms);
+    let mismatch_count = mismatches.len();
+    mismatches.truncate(MAX_REPORTED_MISMATCHES);
+
+    TraceReport {
+        matches: mismatch_count == 0,
+        golden_writes: golden.len(),
+        actual_writes: actual.len(),
+        mismatch_count,
+        mismatches,
+    }
+}
+
+fn by_channel(entries: &[TraceEntry]) -> BTreeMap<(String, u8), ChannelTrace> {
+    let mut channels: BTreeMap<(String, u8), ChannelTrace> = BTreeMap::new();
+    for entry in entries {
+        channels
+            .entry((entry.controller.clone(), entry.channel))
+            .or_default()
+            .push((entry.timestamp_ms, entry.value));
+    }
+    for trace in channels.values_mut() {
+        trace.sort_by_key(|(at, _)| *at);
+    }
+    channels
+}
+
+// What the channel was last set to at `at`, None if it hadn't been written yet
+fn value_at(trace: &ChannelTrace, at: u64) -> Option<Option<u16>> {
+    trace
+        .iter()
+        .take_while(|(written_at, _)| *written_at <= at)
+        .last()
+        .map(|(_, value)| *value)
+}
+
+// Whether the channel passed through `value` within the time tolerance of `at`. A servo
+// sweeps across everything between the values written around then, and either run's ticks
+// can stop up to one step short of where the other's landed (at the top of a reversal, say).
+// A channel that hadn't been written yet could be holding anything
+fn holds_near(
+    trace: &ChannelTrace,
+    at: u64,
+    value: Option<u16>,
+    tolerance: TraceTolerance,
+) -> bool {
+    let from = at.saturating_sub(tolerance.time_ms);
+    let to = at + tolerance.time_ms;
+
+    let held = match value_at(trace, from) {
+        Some(held) => held,
+        None => return trace.first().is_some_and(|(first_at, _)| *first_at <= to),
+    };
+    let candidates: Vec<Option<u16>> = std::iter::once(held)
+        .chain(
+            trace
+                .iter()
+                .filter(|(written_at, _)| (from..=to).contains(written_at))
+                .map(|(_, value)| *value),
+        )
+        .collect();
+
+    let value = match value {
+        Some(value) => value,
+        None => return candidates.contains(&None),
+    };
+    let pulses: Vec<u16> = candidates.iter().flatten().copied().collect();
+    let (low, high) = match (pulses.iter().min(), pulses.iter().max()) {
+        (Some(&low), Some(&high)) => (low, high),
+        _ => return false,
+    };
+    let step = pulses
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .max()
+        .unwrap_or(0);
+    let margin = step + tolerance.pulse;
+    (low.saturating_sub(margin)..=high.saturating_add(margin)).contains(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::servo::simulated::SimulatedPwmDriver;
+
+    fn entry(timestamp_ms: u64, channel: u8, value: Option<u16>) -> TraceEntry {
+        TraceEntry {
+            timestamp_ms,
+            unix_ms: 0,
+            controller: "dome".to_string(),
+            address: "0x40".to_string(),
+            channel,
+            register: format!("0x{:02x}", on_register(channel)),
+            value,
+        }
+    }
+
+    // A pie panel opening in three ticks, then relaxing
+    fn opening() -> Vec<TraceEntry> {
+        vec![
+            entry(0, 0, Some(200)),
+            entry(20, 0, Some(350)),
+            entry(40, 0, Some(500)),
+            entry(200, 0, None),
+        ]
+    }
+
+    fn shifted(entries: &[TraceEntry], by_ms: u64) -> Vec<TraceEntry> {
+        entries
+            .iter()
+            .map(|entry| TraceEntry {
+                timestamp_ms: entry.timestamp_ms + by_ms,
+                ..entry.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_traces_match() {
+        let report = compare_traces(&opening(), &opening(), TraceTolerance::default());
+        assert!(report.matches);
+        assert_eq!(report.golden_writes, 4);
+        assert_eq!(report.actual_writes, 4);
+    }
+
+    #[test]
+    fn timing_drift_only_matches_within_the_tolerance() {
+        let tolerance = TraceTolerance::default();
+        let late = compare_traces(&opening(), &shifted(&opening(), 15), tolerance);
+        assert!(late.matches);
+
+        let much_later = compare_traces(&opening(), &shifted(&opening(), 150), tolerance);
+        assert!(!much_later.matches);
+        assert!(much_later
+            .mismatches
+            .iter()
+            .any(|mismatch| mismatch.expected == Some(200) && mismatch.actual.is_none()));
+    }
+
+    #[test]
+    fn a_pulse_off_by_more_than_a_step_is_reported() {
+        let mut actual = opening();
+        actual[3] = entry(200, 0, Some(700));
+
+        let report = compare_traces(&opening(), &actual, TraceTolerance::default());
+        assert!(!report.matches);
+        assert_eq!(report.mismatches[0].timestamp_ms, 200);
+    }
+
+    #[test]
+    fn a_missing_or_extra_channel_is_reported() {
+        let mut golden = opening();
+        golden.push(entry(100, 1, Some(300)));
+
+        let missing = compare_traces(&golden, &opening(), TraceTolerance::default());
+        assert_eq!(missing.mismatch_count, 1);
+        assert_eq!(missing.mismatches[0].channel, 1);
+        assert_eq!(missing.mismatches[0].expected, Some(300));
+        assert_eq!(missing.mismatches[0].actual, None);
+
+        let extra = compare_traces(&opening(), &golden, TraceTolerance::default());
+        assert_eq!(extra.mismatch_count, 1);
+        assert_eq!(extra.mismatches[0].actual, Some(300));
+    }
+
+    #[test]
+    fn paths_cannot_leave_the_trace_directory() {
+        let recorder = TraceRecorder::new("traces");
+        for path in [
+            "",
+            "/etc/passwd",
+            "../config.json",
+            "golden/../../config.json",
+            "./a",
+        ] {
+            assert!(recorder.resolve(path).is_err(), "{}", path);
+        }
+        assert_eq!(
+            recorder.resolve("golden/open.jsonl").unwrap(),
+            PathBuf::from("traces/golden/open.jsonl")
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_record_the_register_they_started_at() {
+        let config = serde_json::from_value(serde_json::json!({
+            "id": "dome",
+            "i2c_address": "0x40",
+            "frequency": 50,
+            "driver": "simulated"
+        }))
+        .unwrap();
+        let recorder = Arc::new(TraceRecorder::new("traces"));
+        let mut backend = TracedBackend::new(
+            Box::new(SimulatedPwmDriver::new(config).unwrap()),
+            recorder.clone(),
+        );
+
+        let capture = recorder.start_capture();
+        backend.set_pulses(&[(5, 300), (2, 400)]).await.unwrap();
+        backend.set_full_off(1).await.unwrap();
+        let entries = recorder.finish_capture(capture);
+
+        let registers: Vec<(u8, &str)> = entries
+            .iter()
+            .map(|entry| (entry.channel, entry.register.as_str()))
+            .collect();
+        assert_eq!(registers, [(5, "0x0e"), (2, "0x0e"), (1, "0x0c")]);
+        assert!(entries.iter().all(|entry| entry.unix_ms > 0));
+    }
+}
@@ -1,7 +1,7 @@
 use actix_web::web::Data;
 use actix_web::{middleware, web, App, HttpServer};
-use log::{error, info, LevelFilter};
-use serde::Deserialize;
+use log::{error, info, warn, LevelFilter};
+use std::collections::BTreeSet;
 use std::fs;
 use std::sync::Arc;
 
@@ -10,33 +10,24 @@ mod config;
 mod errors;
 mod hardware;
 mod managers;
+mod traits;
 
 use crate::api::handlers::RoutineHandler;
+use crate::config::app_config::{Config, ConfigStore};
 use crate::errors::hardware_error::HardwareError;
-use crate::hardware::audio::config::AudioConfig;
-use crate::hardware::servo::config::{Pca9685Config, ServoConfig};
+use crate::hardware::servo::config::DriverKind;
 use crate::managers::audio_manager::AudioManager;
+use crate::managers::led_manager::LedManager;
+use crate::managers::output_manager::OutputManager;
+use crate::managers::pose_manager::PoseManager;
 use crate::managers::routine_manager::RoutineManager;
 use crate::managers::servo_manager::ServoManager;
 
-#[derive(Deserialize)]
-struct Config {
-    controllers: Vec<Pca9685Config>,
-    servos: Vec<ServoConfig>,
-    server: ServerConfig,
-    audio: AudioConfig,
-}
-
-#[derive(Deserialize)]
-struct ServerConfig {
-    host: String,
-    port: u16,
-    log_level: String,
-}
-
 async fn initialize_hardware(
     config: &Config,
     servo_manager_data: &Data<ServoManager>,
+    led_manager_data: &Data<LedManager>,
+    output_manager_data: &Data<OutputManager>,
 ) -> Result<(), HardwareError> {
     // Initialize PCA9685 controllers
     for controller_config in &config.controllers {
@@ -49,37 +40,118 @@ async fn initialize_hardware(
     // Initialize servos
     for servo_config in &config.servos {
         info!("Initializing servo: {}", servo_config.name);
+        if servo_config.uses_raw_ticks() {
+            // Ticks tuned back when the prescale was fixed at about 60 Hz come out about 20%
+            // longer at a true 50 Hz
+            warn!(
+                "Servo '{}' has its pulse range in raw ticks, which only hold at the frequency they were tuned at. Prefer min_pulse_us/max_pulse_us",
+                servo_config.name
+            );
+        }
         servo_manager_data.add_servo(servo_config.clone()).await?;
     }
 
+    // Initialize servo groups
+    for group_config in &config.groups {
+        info!("Initializing servo group: {}", group_config.name);
+        servo_manager_data.add_group(group_config.clone()).await?;
+    }
+
+    // Initialize interlocks between servos
+    for interlock_config in &config.interlocks {
+        info!("Initializing interlock: {}", interlock_config.name);
+        servo_manager_data
+            .add_interlock(interlock_config.clone())
+            .await?;
+    }
+
+    // Initialize LEDs on spare controller channels
+    for led_config in &config.leds {
+        info!("Initializing LED: {}", led_config.name);
+        led_manager_data.add_led(led_config.clone()).await?;
+    }
+
+    // Initialize relay and switch outputs in their safe state
+    for output_config in &config.digital_outputs {
+        info!("Initializing digital output: {}", output_config.name);
+        output_manager_data
+            .add_output(output_config.clone())
+            .await?;
+    }
+
     Ok(())
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Load configuration
-    let config = load_config()?;
+    let config_store = load_config()?;
+    let config = config_store.config().await;
+    let config_store_data = web::Data::new(config_store);
 
     // Setup logging
     setup_logging(&config.server.log_level);
     info!("Starting Astromech control system...");
 
-    // Check I2C setup
-    if let Err(e) = check_i2c_setup().await {
-        error!("I2C setup check failed: {}", e);
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
+    // Check every I2C bus a real controller sits on
+    let buses: BTreeSet<String> = config
+        .controllers
+        .iter()
+        .filter(|controller| controller.driver == DriverKind::Pca9685)
+        .map(|controller| controller.bus_path())
+        .collect();
+    for bus in &buses {
+        if let Err(e) = check_i2c_setup(bus).await {
+            error!("I2C setup check failed: {}", e);
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
+        }
     }
 
     // Initialize managers
-    let servo_manager = ServoManager::new();
+    let servo_manager = ServoManager::new(&config.trace);
     let servo_manager_data = web::Data::new(servo_manager);
 
+    // Every move and fade is driven from here, so it runs before anything is initialized
+    let tick_interval = config.motion.tick_interval().map_err(|e| {
+        error!("Invalid motion config: {}", e);
+        std::io::Error::new(std::io::ErrorKind::Other, e)
+    })?;
+    tokio::spawn(
+        servo_manager_data
+            .get_ref()
+            .clone()
+            .run_motion(tick_interval),
+    );
+
+    // Record from the very first write, for looking into reports after the fact
+    if let Some(path) = &config.trace.record_path {
+        if let Err(e) = servo_manager_data.trace().start_file(path) {
+            error!("Failed to start I2C trace: {}", e);
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
+        }
+    }
+    let led_manager_data = web::Data::new(LedManager::new(servo_manager_data.clone()));
+    let output_manager_data = web::Data::new(OutputManager::new(servo_manager_data.clone()));
+
     // Initialize hardware using the wrapped servo manager
-    if let Err(e) = initialize_hardware(&config, &servo_manager_data).await {
+    if let Err(e) = initialize_hardware(
+        &config,
+        &servo_manager_data,
+        &led_manager_data,
+        &output_manager_data,
+    )
+    .await
+    {
         error!("Failed to initialize hardware: {}", e);
         return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
     }
 
+    // Relax servos that sit still past their idle_relax_after_ms
+    tokio::spawn(servo_manager_data.get_ref().clone().run_idle_relax());
+
+    // Bring controllers back after a brown-out or a loose connector
+    tokio::spawn(servo_manager_data.get_ref().clone().run_health_checks());
+
     // Initialize audio manager
     let audio_manager = AudioManager::new(config.audio.clone()).map_err(|e| {
         error!("Failed to initialize audio manager: {}", e);
@@ -87,11 +159,25 @@ async fn main() -> std::io::Result<()> {
     })?;
     let audio_manager_data = web::Data::new(audio_manager);
 
+    // Initialize pose manager
+    let pose_manager =
+        PoseManager::new(config.poses.clone(), servo_manager_data.clone()).map_err(|e| {
+            error!("Failed to initialize pose manager: {}", e);
+            std::io::Error::new(std::io::ErrorKind::Other, e)
+        })?;
+    let pose_manager_data = web::Data::new(pose_manager);
+
     let routine_manager = Arc::new(RoutineManager::new(
         servo_manager_data.clone(),
         audio_manager_data.clone(),
+        pose_manager_data.clone(),
+        led_manager_data.clone(),
+        output_manager_data.clone(),
     ));
 
+    // Registered on its own as well, for the emergency stop
+    let routine_manager_data = web::Data::from(routine_manager.clone());
+
     // Wrap `RoutineManager` in `RoutineHandler` and register it in `web::Data`
     let routine_handler = web::Data::new(RoutineHandler::new(routine_manager));
 
@@ -106,7 +192,12 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(servo_manager_data.clone())
             .app_data(audio_manager_data.clone())
+            .app_data(pose_manager_data.clone())
+            .app_data(led_manager_data.clone())
+            .app_data(output_manager_data.clone())
+            .app_data(routine_manager_data.clone())
             .app_data(routine_handler.clone())
+            .app_data(config_store_data.clone())
             .wrap(middleware::Logger::default())
             .wrap(middleware::Compress::default())
             .wrap(
@@ -125,14 +216,11 @@ async fn main() -> std::io::Result<()> {
     server.run().await
 }
 
-fn load_config() -> std::io::Result<Config> {
+fn load_config() -> std::io::Result<ConfigStore> {
     let config_path =
         std::env::var("ASTROMECH_CONFIG").unwrap_or_else(|_| "astromech_config.json".to_string());
 
-    let config_data = fs::read_to_string(&config_path)?;
-
-    serde_json::from_str(&config_data)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    ConfigStore::load(&config_path)
 }
 
 fn setup_logging(log_level: &str) {
@@ -150,33 +238,35 @@ fn setup_logging(log_level: &str) {
         .init();
 }
 
-async fn check_i2c_setup() -> Result<(), HardwareError> {
-    let i2c_path = "/dev/i2c-1";
-
+async fn check_i2c_setup(i2c_path: &str) -> Result<(), HardwareError> {
     // Check if I2C device exists
     if !std::path::Path::new(i2c_path).exists() {
-        error!("I2C device not found. Please ensure I2C is enabled:");
+        error!(
+            "I2C device {} not found. Please ensure I2C is enabled:",
+            i2c_path
+        );
         error!("1. Run 'sudo raspi-config'");
         error!("2. Go to Interface Options -> I2C -> Enable");
         error!("3. Reboot the system");
-        return Err(HardwareError::InitializationError(
-            "I2C device not found".to_string(),
-        ));
+        return Err(HardwareError::InitializationError(format!(
+            "I2C device {} not found",
+            i2c_path
+        )));
     }
 
     // Try to open the device to check access
     match fs::OpenOptions::new().read(true).write(true).open(i2c_path) {
         Ok(_) => {
-            info!("I2C device access verified");
+            info!("I2C device {} access verified", i2c_path);
             Ok(())
         }
         Err(e) => {
-            error!("Cannot access I2C device. Please run:");
+            error!("Cannot access I2C device {}. Please run:", i2c_path);
             error!("sudo chown root:gpio {}", i2c_path);
             error!("sudo chmod 666 {}", i2c_path);
             Err(HardwareError::InitializationError(format!(
-                "Cannot access I2C device: {}",
-                e
+                "Cannot access I2C device {}: {}",
+                i2c_path, e
             )))
         }
     }
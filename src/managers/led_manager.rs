@@ -0,0 +1,303 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::led::state::{LedEffect, LedState, LedStatus};
+use crate::hardware::led::{BlinkPattern, LedConfig};
+use crate::hardware::servo::motion::Sweep;
+use crate::hardware::servo::state::now_ms;
+use crate::hardware::servo::Easing;
+use crate::managers::servo_manager::ServoManager;
+use actix_web::web::Data;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+// Drives LEDs wired to spare PCA9685 channels, with brightness as the duty cycle
+#[derive(Clone)]
+pub struct LedManager {
+    servo_manager: Data<ServoManager>,
+    leds: Arc<Mutex<HashMap<String, LedConfig>>>,
+    states: Arc<Mutex<HashMap<String, LedState>>>,
+}
+
+impl LedManager {
+    pub fn new(servo_manager: Data<ServoManager>) -> Self {
+        Self {
+            servo_manager,
+            leds: Arc::new(Mutex::new(HashMap::new())),
+            states: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn add_led(&self, config: LedConfig) -> Result<(), HardwareError> {
+        if !config.gamma.is_finite() || config.gamma <= 0.0 {
+            return Err(HardwareError::InvalidParameter(format!(
+                "LED '{}' has an invalid gamma of {}",
+                config.name, config.gamma
+            )));
+        }
+
+        if self.leds.lock().await.contains_key(&config.name) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "LED '{}' already exists",
+                config.name
+            )));
+        }
+
+        self.servo_manager
+            .claim_channel(&config.controller_id, config.channel, &config.name)
+            .await?;
+
+        // Start dark so the reported state matches the channel
+        self.states
+            .lock()
+            .await
+            .insert(config.name.clone(), LedState::default());
+        self.leds
+            .lock()
+            .await
+            .insert(config.name.clone(), config.clone());
+        self.write(&config, 0.0).await
+    }
+
+    pub async fn list_leds(&self) -> HashMap<String, LedStatus> {
+        let leds = self.leds.lock().await;
+        let states = self.states.lock().await;
+        leds.values()
+            .map(|config| {
+                let state = states
+                    .get(&config.name)
+                    .map(LedState::current)
+                    .unwrap_or_default();
+                (
+                    config.name.clone(),
+                    LedStatus {
+                        config: config.clone(),
+                        state,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub async fn get_led(&self, name: &str) -> Result<LedStatus, HardwareError> {
+        let config = self.led_config(name).await?;
+        let state = self
+            .states
+            .lock()
+            .await
+            .get(name)
+            .map(LedState::current)
+            .unwrap_or_default();
+        Ok(LedStatus { config, state })
+    }
+
+    // Sets brightness in percent, fading over `duration_ms` when it is non-zero
+    pub async fn set_brightness(
+        &self,
+        name: &str,
+        brightness: f64,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<(), HardwareError> {
+        let config = self.led_config(name).await?;
+        validate_brightness(brightness)?;
+
+        let effect = if duration_ms > 0 {
+            LedEffect::Fading
+        } else {
+            LedEffect::Steady
+        };
+        let (start, generation) = self.claim(name, effect).await;
+
+        let result = self
+            .fade(&config, start, brightness, duration_ms, easing, generation)
+            .await;
+        self.settle(name, generation, &result).await;
+        result
+    }
+
+    // Starts blinking in the background and returns straight away
+    pub async fn blink(&self, name: &str, pattern: BlinkPattern) -> Result<(), HardwareError> {
+        let config = self.led_config(name).await?;
+        validate_brightness(pattern.brightness)?;
+        if pattern.on_ms == 0 || pattern.off_ms == 0 {
+            return Err(HardwareError::InvalidParameter(
+                "Blink on_ms and off_ms must be greater than 0".to_string(),
+            ));
+        }
+
+        let (_, generation) = self.claim(name, LedEffect::Blinking).await;
+        info!(
+            "Blinking LED '{}' {}ms on / {}ms off ({})",
+            name,
+            pattern.on_ms,
+            pattern.off_ms,
+            pattern
+                .count
+                .map(|count| format!("{} times", count))
+                .unwrap_or_else(|| "until stopped".to_string())
+        );
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let result = manager.run_blink(&config, pattern, generation).await;
+            if let Err(e) = &result {
+                warn!("Blinking LED '{}' failed: {}", config.name, e);
+            }
+            manager.settle(&config.name, generation, &result).await;
+        });
+
+        Ok(())
+    }
+
+    async fn run_blink(
+        &self,
+        config: &LedConfig,
+        pattern: BlinkPattern,
+        generation: u64,
+    ) -> Result<(), HardwareError> {
+        let mut blinks = 0;
+        while pattern.count.is_none_or(|count| blinks < count) {
+            for (brightness, hold_ms) in
+                [(pattern.brightness, pattern.on_ms), (0.0, pattern.off_ms)]
+            {
+                if self.is_superseded(&config.name, generation).await {
+                    debug!("Blinking of LED '{}' superseded", config.name);
+                    return Ok(());
+                }
+                self.write(config, brightness).await?;
+                tokio::time::sleep(Duration::from_millis(hold_ms)).await;
+            }
+            blinks += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn fade(
+        &self,
+        config: &LedConfig,
+        start: f64,
+        brightness: f64,
+        duration_ms: u64,
+        easing: Easing,
+        generation: u64,
+    ) -> Result<(), HardwareError> {
+        if duration_ms == 0 {
+            info!("Setting LED '{}' to {}%", config.name, brightness);
+            return self.write(config, brightness).await;
+        }
+
+        info!(
+            "Fading LED '{}' from {}% to {}% over {}ms ({:?})",
+            config.name, start, brightness, duration_ms, easing
+        );
+
+        // Interpolating brightness rather than duty keeps the fade even to the eye
+        let fade = Sweep::new(start, brightness, duration_ms, easing);
+        if let Some(state) = self.states.lock().await.get_mut(&config.name) {
+            if state.generation == generation {
+                state.fade = Some(fade);
+            }
+        }
+
+        let duty_config = config.clone();
+        let result = self
+            .servo_manager
+            .run_sweep(&config.controller_id, config.channel, fade, move |level| {
+                led_duty(&duty_config, level)
+            })
+            .await;
+
+        // A fade that was replaced leaves the state to whatever replaced it
+        let mut states = self.states.lock().await;
+        if let Some(state) = states.get_mut(&config.name) {
+            if state.generation == generation {
+                state.brightness = match &result {
+                    Ok(()) => brightness,
+                    Err(_) => state.current().brightness,
+                };
+                state.fade = None;
+                state.last_change_ms = Some(now_ms());
+            }
+        }
+        result
+    }
+
+    async fn led_config(&self, name: &str) -> Result<LedConfig, HardwareError> {
+        self.leds
+            .lock()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| HardwareError::NotFound(format!("LED '{}' not found", name)))
+    }
+
+    // Takes over the LED from any running effect; returns its brightness and the new generation
+    async fn claim(&self, name: &str, effect: LedEffect) -> (f64, u64) {
+        let mut states = self.states.lock().await;
+        let state = states.entry(name.to_string()).or_default();
+        // Pick up from wherever a running fade has got to
+        *state = state.current();
+        state.fade = None;
+        state.generation += 1;
+        state.effect = effect;
+        state.last_error = None;
+        (state.brightness, state.generation)
+    }
+
+    async fn settle(&self, name: &str, generation: u64, result: &Result<(), HardwareError>) {
+        let mut states = self.states.lock().await;
+        if let Some(state) = states.get_mut(name) {
+            if state.generation == generation {
+                state.effect = LedEffect::Steady;
+                if let Err(e) = result {
+                    state.last_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    async fn is_superseded(&self, name: &str, generation: u64) -> bool {
+        let states = self.states.lock().await;
+        states
+            .get(name)
+            .is_none_or(|state| state.generation != generation)
+    }
+
+    async fn write(&self, config: &LedConfig, brightness: f64) -> Result<(), HardwareError> {
+        let duty_config = config.clone();
+        self.servo_manager
+            .run_sweep(
+                &config.controller_id,
+                config.channel,
+                Sweep::hold(brightness),
+                move |level| led_duty(&duty_config, level),
+            )
+            .await?;
+
+        let mut states = self.states.lock().await;
+        if let Some(state) = states.get_mut(&config.name) {
+            state.brightness = brightness;
+            state.last_change_ms = Some(now_ms());
+        }
+        Ok(())
+    }
+}
+
+fn validate_brightness(brightness: f64) -> Result<(), HardwareError> {
+    if !(0.0..=100.0).contains(&brightness) {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Brightness {} is outside valid range [0, 100]",
+            brightness
+        )));
+    }
+    Ok(())
+}
+
+// Dark uses the full-off bit rather than a zero-length pulse
+fn led_duty(config: &LedConfig, brightness: f64) -> Option<u16> {
+    let duty = config.duty_ticks(brightness);
+    (duty > 0).then_some(duty)
+}
@@ -1,4 +1,7 @@
 mod astromech_manager;
 pub mod audio_manager;
+pub mod led_manager;
+pub mod output_manager;
+pub mod pose_manager;
 pub mod routine_manager;
 pub mod servo_manager;
@@ -0,0 +1,237 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::output::state::{DigitalOutputState, DigitalOutputStatus};
+use crate::hardware::output::DigitalOutputConfig;
+use crate::hardware::servo::config::PWM_RESOLUTION;
+use crate::hardware::servo::motion::Sweep;
+use crate::hardware::servo::state::now_ms;
+use crate::managers::servo_manager::ServoManager;
+use actix_web::web::Data;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+// Switches relays and MOSFETs wired to spare PCA9685 channels fully on or off
+#[derive(Clone)]
+pub struct OutputManager {
+    servo_manager: Data<ServoManager>,
+    outputs: Arc<Mutex<HashMap<String, DigitalOutputConfig>>>,
+    states: Arc<Mutex<HashMap<String, DigitalOutputState>>>,
+}
+
+impl OutputManager {
+    pub fn new(servo_manager: Data<ServoManager>) -> Self {
+        Self {
+            servo_manager,
+            outputs: Arc::new(Mutex::new(HashMap::new())),
+            states: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn add_output(&self, config: DigitalOutputConfig) -> Result<(), HardwareError> {
+        if self.outputs.lock().await.contains_key(&config.name) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Output '{}' already exists",
+                config.name
+            )));
+        }
+
+        self.servo_manager
+            .claim_channel(&config.controller_id, config.channel, &config.name)
+            .await?;
+
+        self.states
+            .lock()
+            .await
+            .insert(config.name.clone(), DigitalOutputState::default());
+        self.outputs
+            .lock()
+            .await
+            .insert(config.name.clone(), config.clone());
+
+        // Never leave a relay floating in whatever state the board powered up in
+        self.write(&config, config.default_on).await
+    }
+
+    pub async fn list_outputs(&self) -> HashMap<String, DigitalOutputStatus> {
+        let outputs = self.outputs.lock().await;
+        let states = self.states.lock().await;
+        outputs
+            .values()
+            .map(|config| {
+                let state = states.get(&config.name).cloned().unwrap_or_default();
+                (
+                    config.name.clone(),
+                    DigitalOutputStatus {
+                        config: config.clone(),
+                        state,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub async fn get_output(&self, name: &str) -> Result<DigitalOutputStatus, HardwareError> {
+        let config = self.output_config(name).await?;
+        let state = self
+            .states
+            .lock()
+            .await
+            .get(name)
+            .cloned()
+            .unwrap_or_default();
+        Ok(DigitalOutputStatus { config, state })
+    }
+
+    pub async fn set_output(&self, name: &str, on: bool) -> Result<(), HardwareError> {
+        let config = self.output_config(name).await?;
+        self.ensure_not_stopped(&config, on).await?;
+
+        let generation = self.claim(name, None).await;
+        info!(
+            "Switching output '{}' {}",
+            name,
+            if on { "on" } else { "off" }
+        );
+        let result = self.write(&config, on).await;
+        self.settle(name, generation, &result).await;
+        result
+    }
+
+    // Flips the output away from its safe state for `duration_ms`, then back. Returns once the
+    // pulse is over
+    pub async fn pulse(&self, name: &str, duration_ms: u64) -> Result<(), HardwareError> {
+        let config = self.output_config(name).await?;
+        if duration_ms == 0 {
+            return Err(HardwareError::InvalidParameter(
+                "Pulse duration must be greater than 0".to_string(),
+            ));
+        }
+        self.ensure_not_stopped(&config, !config.default_on).await?;
+
+        let generation = self.claim(name, Some(now_ms() + duration_ms)).await;
+        info!("Pulsing output '{}' for {}ms", name, duration_ms);
+
+        let result = self.run_pulse(&config, duration_ms, generation).await;
+        self.settle(name, generation, &result).await;
+        result
+    }
+
+    // Puts every output back in its safe state, e.g. on an emergency stop
+    pub async fn reset_all(&self) -> Result<(), HardwareError> {
+        let outputs: Vec<DigitalOutputConfig> =
+            self.outputs.lock().await.values().cloned().collect();
+
+        let mut last_error = None;
+        for config in outputs {
+            // Cancels any running pulse as well
+            let generation = self.claim(&config.name, None).await;
+            let result = self.write(&config, config.default_on).await;
+            if let Err(e) = &result {
+                warn!("Could not reset output '{}': {}", config.name, e);
+            }
+            self.settle(&config.name, generation, &result).await;
+            if let Err(e) = result {
+                last_error = Some(e);
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    async fn run_pulse(
+        &self,
+        config: &DigitalOutputConfig,
+        duration_ms: u64,
+        generation: u64,
+    ) -> Result<(), HardwareError> {
+        self.write(config, !config.default_on).await?;
+        tokio::time::sleep(Duration::from_millis(duration_ms)).await;
+
+        if self.is_superseded(&config.name, generation).await {
+            debug!("Pulse of output '{}' superseded", config.name);
+            return Ok(());
+        }
+        self.write(config, config.default_on).await
+    }
+
+    // Switching anything away from its safe state waits until the emergency stop is cleared
+    async fn ensure_not_stopped(
+        &self,
+        config: &DigitalOutputConfig,
+        on: bool,
+    ) -> Result<(), HardwareError> {
+        if on == config.default_on {
+            return Ok(());
+        }
+        if let Some(estop) = self.servo_manager.estop_status().await {
+            return Err(HardwareError::InvalidState(format!(
+                "Emergency stop ({:?}) is engaged; output '{}' stays in its safe state",
+                estop.mode, config.name
+            )));
+        }
+        Ok(())
+    }
+
+    async fn output_config(&self, name: &str) -> Result<DigitalOutputConfig, HardwareError> {
+        self.outputs
+            .lock()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| HardwareError::NotFound(format!("Output '{}' not found", name)))
+    }
+
+    // Takes over the output from any running pulse; returns the new generation
+    async fn claim(&self, name: &str, pulse_until_ms: Option<u64>) -> u64 {
+        let mut states = self.states.lock().await;
+        let state = states.entry(name.to_string()).or_default();
+        state.generation += 1;
+        state.pulse_until_ms = pulse_until_ms;
+        state.last_error = None;
+        state.generation
+    }
+
+    async fn settle(&self, name: &str, generation: u64, result: &Result<(), HardwareError>) {
+        let mut states = self.states.lock().await;
+        if let Some(state) = states.get_mut(name) {
+            if state.generation == generation {
+                state.pulse_until_ms = None;
+                if let Err(e) = result {
+                    state.last_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    async fn is_superseded(&self, name: &str, generation: u64) -> bool {
+        let states = self.states.lock().await;
+        states
+            .get(name)
+            .is_none_or(|state| state.generation != generation)
+    }
+
+    // Through the motion loop like every other channel write
+    async fn write(&self, config: &DigitalOutputConfig, on: bool) -> Result<(), HardwareError> {
+        let output = config.channel_high(on).then_some(PWM_RESOLUTION as u16);
+        self.servo_manager
+            .run_sweep(
+                &config.controller_id,
+                config.channel,
+                Sweep::hold(0.0),
+                move |_| output,
+            )
+            .await?;
+
+        let mut states = self.states.lock().await;
+        if let Some(state) = states.get_mut(&config.name) {
+            state.on = on;
+            state.last_change_ms = Some(now_ms());
+        }
+        Ok(())
+    }
+}
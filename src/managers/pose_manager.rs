@@ -0,0 +1,163 @@
+use crate::config::app_config::write_atomic;
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::config::PoseConfig;
+use crate::hardware::servo::motion::MoveOutcome;
+use crate::hardware::servo::Easing;
+use crate::managers::servo_manager::ServoManager;
+use actix_web::web::Data;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use tokio::sync::Mutex;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Pose {
+    pub name: String,
+    pub positions: BTreeMap<String, f64>, // Servo name to angle
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+pub struct PoseManager {
+    config: PoseConfig,
+    servo_manager: Data<ServoManager>,
+    poses: Mutex<HashMap<String, Pose>>,
+}
+
+impl PoseManager {
+    pub fn new(
+        config: PoseConfig,
+        servo_manager: Data<ServoManager>,
+    ) -> Result<Self, HardwareError> {
+        let poses = load_poses(&config.file)?;
+        info!("Loaded {} poses from {}", poses.len(), config.file);
+
+        Ok(Self {
+            config,
+            servo_manager,
+            poses: Mutex::new(poses),
+        })
+    }
+
+    pub async fn list_poses(&self) -> Vec<Pose> {
+        let poses = self.poses.lock().await;
+        let mut poses: Vec<Pose> = poses.values().cloned().collect();
+        poses.sort_by(|a, b| a.name.cmp(&b.name));
+        poses
+    }
+
+    pub async fn get_pose(&self, name: &str) -> Result<Pose, HardwareError> {
+        let poses = self.poses.lock().await;
+        poses
+            .get(name)
+            .cloned()
+            .ok_or_else(|| HardwareError::NotFound(format!("Pose '{}' not found", name)))
+    }
+
+    // Captures the current angle of `servos`, or of every servo with a known position
+    pub async fn save_pose(
+        &self,
+        name: &str,
+        servos: Option<Vec<String>>,
+        description: Option<String>,
+    ) -> Result<Pose, HardwareError> {
+        let statuses = self.servo_manager.list_servos().await;
+
+        let positions = match servos {
+            Some(servos) => {
+                let mut positions = BTreeMap::new();
+                for servo in servos {
+                    let status = statuses.get(&servo).ok_or_else(|| {
+                        HardwareError::NotFound(format!("Servo '{}' not found", servo))
+                    })?;
+                    let angle = status.state.angle.ok_or_else(|| {
+                        HardwareError::InvalidState(format!(
+                            "Servo '{}' has no known position",
+                            servo
+                        ))
+                    })?;
+                    positions.insert(servo, angle);
+                }
+                positions
+            }
+            None => statuses
+                .iter()
+                .filter_map(|(servo, status)| {
+                    status.state.angle.map(|angle| (servo.clone(), angle))
+                })
+                .collect(),
+        };
+
+        if positions.is_empty() {
+            return Err(HardwareError::InvalidState(
+                "No servo has a known position to capture".to_string(),
+            ));
+        }
+
+        let pose = Pose {
+            name: name.to_string(),
+            positions,
+            description,
+        };
+
+        let mut poses = self.poses.lock().await;
+        poses.insert(name.to_string(), pose.clone());
+        self.write_poses(&poses)?;
+
+        info!("Saved pose '{}' ({} servos)", name, pose.positions.len());
+        Ok(pose)
+    }
+
+    pub async fn delete_pose(&self, name: &str) -> Result<(), HardwareError> {
+        let mut poses = self.poses.lock().await;
+        if poses.remove(name).is_none() {
+            return Err(HardwareError::NotFound(format!(
+                "Pose '{}' not found",
+                name
+            )));
+        }
+        self.write_poses(&poses)
+    }
+
+    pub async fn recall_pose(
+        &self,
+        name: &str,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<BTreeMap<String, MoveOutcome>, HardwareError> {
+        let pose = self.get_pose(name).await?;
+        info!("Recalling pose '{}' over {}ms", name, duration_ms);
+
+        let targets = pose.positions.into_iter().collect();
+        self.servo_manager
+            .move_servos(targets, duration_ms, easing, 0)
+            .await
+    }
+
+    fn write_poses(&self, poses: &HashMap<String, Pose>) -> Result<(), HardwareError> {
+        let mut poses: Vec<&Pose> = poses.values().collect();
+        poses.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let data = serde_json::to_string_pretty(&poses)
+            .map_err(|e| HardwareError::Other(format!("Failed to serialize poses: {}", e)))?;
+
+        write_atomic(&self.config.file, &data)
+    }
+}
+
+fn load_poses(path: &str) -> Result<HashMap<String, Pose>, HardwareError> {
+    if !Path::new(path).exists() {
+        return Ok(HashMap::new());
+    }
+
+    let data = fs::read_to_string(path)?;
+    let poses: Vec<Pose> = serde_json::from_str(&data)
+        .map_err(|e| HardwareError::Other(format!("Failed to parse poses file: {}", e)))?;
+
+    Ok(poses
+        .into_iter()
+        .map(|pose| (pose.name.clone(), pose))
+        .collect())
+}
@@ -1,20 +1,227 @@
 use crate::api::command::Command;
-use crate::managers::{audio_manager::AudioManager, servo_manager::ServoManager};
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::led::BlinkPattern;
+use crate::hardware::servo::lease::{
+    current_owner, run_as, ConflictPolicy, LeaseRequest, Owner, ROUTINE_OWNER_PREFIX,
+};
+use crate::hardware::servo::state::EstopMode;
+use crate::managers::audio_manager::AudioManager;
+use crate::managers::led_manager::LedManager;
+use crate::managers::output_manager::OutputManager;
+use crate::managers::pose_manager::PoseManager;
+use crate::managers::servo_manager::{GroupMove, ServoManager};
 use actix_web::web::Data;
+use log::{debug, error, warn};
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinSet;
+
+// A queued routine gives up if its servos stay leased for this long
+const LEASE_QUEUE_TIMEOUT_MS: u64 = 30_000;
 
 pub struct RoutineManager {
     servo_manager: Data<ServoManager>,
     audio_manager: Data<AudioManager>,
+    pose_manager: Data<PoseManager>,
+    led_manager: Data<LedManager>,
+    output_manager: Data<OutputManager>,
+    tasks: Mutex<JoinSet<()>>, // Running routines and the commands they spawned
+    next_routine: AtomicU64,
 }
 
 impl RoutineManager {
-    pub fn new(servo_manager: Data<ServoManager>, audio_manager: Data<AudioManager>) -> Self {
+    pub fn new(
+        servo_manager: Data<ServoManager>,
+        audio_manager: Data<AudioManager>,
+        pose_manager: Data<PoseManager>,
+        led_manager: Data<LedManager>,
+        output_manager: Data<OutputManager>,
+    ) -> Self {
         Self {
             servo_manager,
             audio_manager,
+            pose_manager,
+            led_manager,
+            output_manager,
+            tasks: Mutex::new(JoinSet::new()),
+            next_routine: AtomicU64::new(1),
+        }
+    }
+
+    // Leases the servos the routine uses, then runs it in the background. The lease is released
+    // once the routine and everything it started have finished. Returns the routine's owner name
+    pub async fn start_routine(
+        self: Arc<Self>,
+        commands: Vec<Command>,
+        priority: i32,
+        on_conflict: ConflictPolicy,
+    ) -> Result<String, HardwareError> {
+        let (name, _finished) = self.launch(commands, priority, on_conflict).await?;
+        Ok(name)
+    }
+
+    // Like start_routine, but only returns once the routine and everything it started are done
+    pub async fn run_routine(
+        self: Arc<Self>,
+        commands: Vec<Command>,
+        priority: i32,
+        on_conflict: ConflictPolicy,
+    ) -> Result<String, HardwareError> {
+        let (name, finished) = self.launch(commands, priority, on_conflict).await?;
+        let _ = finished.await;
+        Ok(name)
+    }
+
+    async fn launch(
+        self: Arc<Self>,
+        commands: Vec<Command>,
+        priority: i32,
+        on_conflict: ConflictPolicy,
+    ) -> Result<(String, oneshot::Receiver<()>), HardwareError> {
+        if let Some(estop) = self.servo_manager.estop_status().await {
+            return Err(HardwareError::InvalidState(format!(
+                "Emergency stop ({:?}) is engaged; clear it before starting routines",
+                estop.mode
+            )));
+        }
+
+        let name = format!(
+            "{}{}",
+            ROUTINE_OWNER_PREFIX,
+            self.next_routine.fetch_add(1, Ordering::Relaxed)
+        );
+        let servos = self.routine_servos(&commands).await?;
+
+        let leased = !servos.is_empty();
+        if leased {
+            self.servo_manager
+                .acquire_lease(LeaseRequest {
+                    owner: name.clone(),
+                    servos,
+                    priority,
+                    on_conflict,
+                    timeout_ms: Some(LEASE_QUEUE_TIMEOUT_MS),
+                })
+                .await?;
+        }
+
+        // The owner is dropped once the last task acting for it ends
+        let (done, finished) = oneshot::channel();
+        let servo_manager = self.servo_manager.clone();
+        let release_name = name.clone();
+        let owner = Owner::with_release(&name, move || {
+            let _ = done.send(());
+            if !leased {
+                return;
+            }
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(async move {
+                    // Already gone if an emergency stop revoked it
+                    if let Err(e) = servo_manager.release_lease(&release_name).await {
+                        debug!("{}", e);
+                    }
+                });
+            }
+        });
+
+        let routine_manager = Arc::clone(&self);
+        self.spawn(run_as(Some(Arc::new(owner)), async move {
+            routine_manager.execute_routine(commands).await
+        }))
+        .await;
+        Ok((name, finished))
+    }
+
+    // Every servo the routine's commands can move
+    async fn routine_servos(&self, commands: &[Command]) -> Result<Vec<String>, HardwareError> {
+        let mut servos = BTreeSet::new();
+        for command in commands {
+            match command {
+                Command::MoveServo { servo_name, .. }
+                | Command::SetSpeed { servo_name, .. }
+                | Command::ToggleServo { servo_name, .. } => {
+                    servos.insert(servo_name.clone());
+                }
+                Command::MoveServos { positions, .. } => {
+                    servos.extend(positions.keys().cloned());
+                }
+                Command::MoveGroup { group_name, .. } => {
+                    let groups = self.servo_manager.list_groups().await;
+                    let group = groups.get(group_name).ok_or_else(|| {
+                        HardwareError::NotFound(format!("Servo group '{}' not found", group_name))
+                    })?;
+                    servos.extend(group.servos.iter().cloned());
+                }
+                Command::Home {
+                    servo_name: Some(servo_name),
+                    ..
+                } => {
+                    servos.insert(servo_name.clone());
+                }
+                Command::Home {
+                    servo_name: None, ..
+                } => {
+                    let statuses = self.servo_manager.list_servos().await;
+                    servos.extend(
+                        statuses
+                            .into_iter()
+                            .filter(|(_, status)| status.config.home_angle.is_some())
+                            .map(|(name, _)| name),
+                    );
+                }
+                Command::RecallPose { pose_name, .. } => {
+                    let pose = self.pose_manager.get_pose(pose_name).await?;
+                    servos.extend(pose.positions.into_keys());
+                }
+                Command::SetLed { .. }
+                | Command::BlinkLed { .. }
+                | Command::SetOutput { .. }
+                | Command::PulseOutput { .. }
+                | Command::PlayAudio { .. }
+                | Command::Pause { .. } => {}
+            }
         }
+        Ok(servos.into_iter().collect())
+    }
+
+    // Cancels every routine, stops all audio, halts the servos and puts the digital outputs in
+    // their safe state, leaving motion latched off. Returns the number of cancelled tasks
+    pub async fn emergency_stop(&self, mode: EstopMode) -> Result<usize, HardwareError> {
+        // Latch first so nothing a dying routine does can start a new move
+        let servo_result = self.servo_manager.engage_estop(mode).await;
+
+        let cancelled = {
+            let mut tasks = self.tasks.lock().await;
+            while tasks.try_join_next().is_some() {}
+            let running = tasks.len();
+            tasks.abort_all();
+            running
+        };
+
+        if let Err(e) = self.output_manager.reset_all().await {
+            error!("Emergency stop could not reset every output: {}", e);
+        }
+
+        if let Err(e) = self.audio_manager.stop_all().await {
+            warn!("Emergency stop could not stop audio: {}", e);
+        }
+
+        if let Err(e) = &servo_result {
+            error!("Emergency stop could not halt every servo: {}", e);
+        }
+        servo_result.map(|()| cancelled)
+    }
+
+    async fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().await;
+        // Reap finished tasks so the set doesn't grow forever
+        while tasks.try_join_next().is_some() {}
+        // Commands keep acting for the routine that started them
+        tasks.spawn(run_as(current_owner(), task));
     }
 
     pub async fn execute_routine(&self, commands: Vec<Command>) {
@@ -23,21 +230,191 @@ impl RoutineManager {
                 Command::MoveServo {
                     servo_name,
                     position,
-                    duration: _duration,
+                    duration,
+                    easing,
+                } => {
+                    let servo_manager = Arc::clone(&self.servo_manager);
+                    self.spawn(async move {
+                        if let Err(e) = servo_manager
+                            .move_servo_to(&servo_name, &position, duration, easing)
+                            .await
+                        {
+                            error!("Error moving servo: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::SetSpeed {
+                    servo_name,
+                    speed,
+                    duration,
                 } => {
                     let servo_manager = Arc::clone(&self.servo_manager);
-                    tokio::spawn(async move {
-                        servo_manager
-                            .move_servo(&servo_name, position)
+                    self.spawn(async move {
+                        if let Err(e) = servo_manager.set_speed(&servo_name, speed, duration).await
+                        {
+                            error!("Error setting servo speed: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::MoveServos {
+                    positions,
+                    duration,
+                    easing,
+                } => {
+                    let servo_manager = Arc::clone(&self.servo_manager);
+                    self.spawn(async move {
+                        let result = match servo_manager
+                            .resolve_targets(positions.into_iter().collect())
                             .await
-                            .unwrap();
-                    });
+                        {
+                            Ok(targets) => servo_manager
+                                .move_servos_synced(targets, duration, easing)
+                                .await
+                                .map(|_| ()),
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = result {
+                            error!("Error moving servos: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::ToggleServo {
+                    servo_name,
+                    positions,
+                    duration,
+                    easing,
+                } => {
+                    let servo_manager = Arc::clone(&self.servo_manager);
+                    self.spawn(async move {
+                        if let Err(e) = servo_manager
+                            .toggle_servo(&servo_name, positions, duration, easing)
+                            .await
+                        {
+                            error!("Error toggling servo: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::MoveGroup {
+                    group_name,
+                    position,
+                    duration,
+                    easing,
+                    overrides,
+                    stagger_ms,
+                } => {
+                    let servo_manager = Arc::clone(&self.servo_manager);
+                    let group_move = GroupMove {
+                        target: position,
+                        duration_ms: duration,
+                        easing,
+                        overrides,
+                        stagger_ms,
+                    };
+                    self.spawn(async move {
+                        if let Err(e) = servo_manager.move_group(&group_name, group_move).await {
+                            error!("Error moving group: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::Home {
+                    servo_name,
+                    duration,
+                    easing,
+                } => {
+                    let servo_manager = Arc::clone(&self.servo_manager);
+                    self.spawn(async move {
+                        let result = match servo_name {
+                            Some(servo_name) => servo_manager
+                                .home_servo(&servo_name, duration, easing)
+                                .await
+                                .map(|_| ()),
+                            None => servo_manager.home_all(duration, easing).await.map(|_| ()),
+                        };
+                        if let Err(e) = result {
+                            error!("Error homing servos: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::RecallPose {
+                    pose_name,
+                    duration,
+                    easing,
+                } => {
+                    let pose_manager = Arc::clone(&self.pose_manager);
+                    self.spawn(async move {
+                        if let Err(e) = pose_manager.recall_pose(&pose_name, duration, easing).await
+                        {
+                            error!("Error recalling pose: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::SetLed {
+                    led_name,
+                    brightness,
+                    duration,
+                    easing,
+                } => {
+                    let led_manager = Arc::clone(&self.led_manager);
+                    self.spawn(async move {
+                        if let Err(e) = led_manager
+                            .set_brightness(&led_name, brightness, duration, easing)
+                            .await
+                        {
+                            error!("Error setting LED: {}", e);
+                        }
+                    })
+                    .await;
+                }
+                Command::BlinkLed {
+                    led_name,
+                    on_ms,
+                    off_ms,
+                    count,
+                    brightness,
+                } => {
+                    let pattern = BlinkPattern {
+                        on_ms,
+                        off_ms,
+                        count,
+                        brightness,
+                    };
+                    // A bad LED shouldn't stop the rest of the routine
+                    if let Err(e) = self.led_manager.blink(&led_name, pattern).await {
+                        error!("Error blinking LED: {}", e);
+                    }
+                }
+                Command::SetOutput { output_name, on } => {
+                    if let Err(e) = self.output_manager.set_output(&output_name, on).await {
+                        error!("Error switching output: {}", e);
+                    }
+                }
+                Command::PulseOutput {
+                    output_name,
+                    duration,
+                } => {
+                    let output_manager = Arc::clone(&self.output_manager);
+                    self.spawn(async move {
+                        if let Err(e) = output_manager.pulse(&output_name, duration).await {
+                            error!("Error pulsing output: {}", e);
+                        }
+                    })
+                    .await;
                 }
                 Command::PlayAudio { file } => {
                     let audio_manager = Arc::clone(&self.audio_manager);
-                    tokio::spawn(async move {
-                        audio_manager.play_audio(&file).await.unwrap();
-                    });
+                    self.spawn(async move {
+                        if let Err(e) = audio_manager.play_audio(&file).await {
+                            error!("Error playing audio: {}", e);
+                        }
+                    })
+                    .await;
                 }
                 Command::Pause { duration } => {
                     tokio::time::sleep(Duration::from_millis(duration)).await;
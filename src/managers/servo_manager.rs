@@ -1,31 +1,159 @@
-use log::{error, info};
-use std::collections::HashMap;
+use log::{debug, error, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Mutex, Notify};
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
 
 use crate::errors::hardware_error::HardwareError;
-use crate::hardware::servo::config::{Pca9685Config, ServoConfig};
-use crate::hardware::servo::Pca9685Controller;
-use pwm_pca9685::Channel;
+use crate::hardware::i2c::{probe_bus, BusScan, ScannedDevice, PCA9685_RANGE};
+use crate::hardware::servo::actor::duplicate;
+use crate::hardware::servo::calibration::{CalibrationMark, CalibrationSession};
+use crate::hardware::servo::config::{
+    i2c_bus_path, DriverKind, InterlockConfig, Pca9685Config, ServoConfig, ServoGroupConfig,
+    ServoKind, ServoTarget, TraceConfig, PWM_RESOLUTION,
+};
+use crate::hardware::servo::health::ControllerStatus;
+use crate::hardware::servo::lease::{current_owner, run_as, ConflictPolicy, Lease, LeaseRequest};
+use crate::hardware::servo::motion::{limit_move, MoveOutcome, Sweep};
+use crate::hardware::servo::simulated::SimulatedBoard;
+use crate::hardware::servo::state::{now_ms, EmergencyStop, EstopMode, ServoState, ServoStatus};
+use crate::hardware::servo::trace::TraceRecorder;
+use crate::hardware::servo::{create_driver, Easing};
+use crate::traits::hardware::PwmDriver;
+
+// How often servos are checked for idle_relax_after_ms
+const IDLE_CHECK_INTERVAL_MS: u64 = 250;
+// How often controllers are checked for a reset or lost connection
+const HEALTH_CHECK_INTERVAL_MS: u64 = 1000;
+
+// Controller id and channel a trajectory drives
+type ChannelKey = (String, u8);
+
+// What one tick writes to a controller
+struct ControllerWrites {
+    controller: Arc<dyn PwmDriver>,
+    pulses: Vec<(u8, u16)>,
+    full_on: Vec<u8>,
+    full_off: Vec<u8>,
+}
+
+// One servo's share of a synchronized batch move
+struct BatchMove {
+    config: ServoConfig,
+    controller: Arc<dyn PwmDriver>,
+    pulse_range: (u16, u16),
+    start_angle: f64,
+    angle: f64,
+    generation: u64,
+    outcome: MoveOutcome,
+}
+
+// A trajectory the motion loop is driving on one channel
+struct Track {
+    id: u64, // Tells a tick whether the track it sampled was replaced while it was writing
+    controller: Arc<dyn PwmDriver>,
+    sweep: Sweep,
+    output: TrackOutput,
+    written: Option<Option<u16>>, // Last output sent, so a channel that didn't change is skipped
+    done: oneshot::Sender<Result<(), HardwareError>>,
+}
+
+enum TrackOutput {
+    // A servo angle, dropped as soon as a newer command claims the servo
+    Servo {
+        config: Box<ServoConfig>,
+        pulse_range: (u16, u16),
+        generation: u64,
+    },
+    // One fixed output for a servo outside an angle sweep: a speed, a calibration pulse or
+    // fully off. Dropped the same way once a newer command claims the servo
+    Fixed {
+        servo: String,
+        generation: u64,
+        output: Option<u16>,
+    },
+    // Any other value, mapped to a pulse width, 4096 for fully on or None for fully off
+    Duty(Box<dyn Fn(f64) -> Option<u16> + Send + Sync>),
+}
+
+impl TrackOutput {
+    // The servo the track drives and the command it was started for
+    fn claim(&self) -> Option<(&str, u64)> {
+        match self {
+            TrackOutput::Servo {
+                config, generation, ..
+            } => Some((config.name.as_str(), *generation)),
+            TrackOutput::Fixed {
+                servo, generation, ..
+            } => Some((servo.as_str(), *generation)),
+            TrackOutput::Duty(_) => None,
+        }
+    }
+}
+
+// What one tick sampled from a track
+struct Sample {
+    key: ChannelKey,
+    id: u64,
+    value: f64,
+    output: Option<u16>,
+    changed: bool,
+    finished: bool,
+}
 
 #[derive(Clone)]
 pub struct ServoManager {
-    controllers: Arc<Mutex<HashMap<String, Arc<Pca9685Controller>>>>,
+    controllers: Arc<Mutex<HashMap<String, Arc<dyn PwmDriver>>>>,
     servos: Arc<Mutex<HashMap<String, ServoConfig>>>,
+    states: Arc<Mutex<HashMap<String, ServoState>>>,
+    groups: Arc<Mutex<HashMap<String, ServoGroupConfig>>>,
+    interlocks: Arc<Mutex<Vec<InterlockConfig>>>,
+    calibrations: Arc<Mutex<HashMap<String, CalibrationSession>>>,
+    estop: Arc<Mutex<Option<EmergencyStop>>>, // Latched until cleared; blocks all motion
+    claims: Arc<Mutex<HashMap<(String, u8), String>>>, // Channels driven by non-servo outputs
+    leases: Arc<Mutex<HashMap<String, Lease>>>, // Servo name to the owner driving it
+    lease_released: Arc<Notify>,
+    tracks: Arc<Mutex<HashMap<ChannelKey, Track>>>, // Trajectories the motion loop is driving
+    next_track: Arc<AtomicU64>,
+    trace: Arc<TraceRecorder>, // Sees every write to every controller
+}
+
+pub struct GroupMove {
+    pub target: ServoTarget, // A position name is looked up on each member
+    pub duration_ms: u64,
+    pub easing: Easing,
+    pub overrides: HashMap<String, ServoTarget>, // Per-member targets replacing `target`
+    pub stagger_ms: Option<u64>,                 // Replaces the group's configured stagger
 }
 
 impl ServoManager {
-    pub fn new() -> Self {
+    pub fn new(trace: &TraceConfig) -> Self {
         Self {
             controllers: Arc::new(Mutex::new(HashMap::new())),
             servos: Arc::new(Mutex::new(HashMap::new())),
+            states: Arc::new(Mutex::new(HashMap::new())),
+            groups: Arc::new(Mutex::new(HashMap::new())),
+            interlocks: Arc::new(Mutex::new(Vec::new())),
+            calibrations: Arc::new(Mutex::new(HashMap::new())),
+            estop: Arc::new(Mutex::new(None)),
+            claims: Arc::new(Mutex::new(HashMap::new())),
+            leases: Arc::new(Mutex::new(HashMap::new())),
+            lease_released: Arc::new(Notify::new()),
+            tracks: Arc::new(Mutex::new(HashMap::new())),
+            next_track: Arc::new(AtomicU64::new(1)),
+            trace: Arc::new(TraceRecorder::new(&trace.directory)),
         }
     }
 
     pub async fn initialize_controller(&self, config: Pca9685Config) -> Result<(), HardwareError> {
-        let controller = Pca9685Controller::new(config.clone())?;
         let mut controllers = self.controllers.lock().await;
-        controllers.insert(config.id.clone(), Arc::new(controller));
+        validate_controller(&controllers, &config)?;
+
+        let controller = create_driver(config.clone(), self.trace.clone())?;
+        controllers.insert(config.id.clone(), controller);
         Ok(())
     }
 
@@ -37,109 +165,2896 @@ impl ServoManager {
             .collect()
     }
 
-    pub async fn add_servo(&self, config: ServoConfig) -> Result<(), HardwareError> {
+    pub async fn controller_statuses(&self) -> HashMap<String, ControllerStatus> {
         let controllers = self.controllers.lock().await;
+        controllers
+            .iter()
+            .map(|(id, controller)| {
+                let status = ControllerStatus {
+                    config: controller.get_config().clone(),
+                    health: controller.health(),
+                    metrics: controller.metrics(),
+                };
+                (id.clone(), status)
+            })
+            .collect()
+    }
+
+    pub async fn add_controller(&self, config: Pca9685Config) -> Result<(), HardwareError> {
+        if self.controllers.lock().await.contains_key(&config.id) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Controller '{}' already exists",
+                config.id
+            )));
+        }
+
+        info!("Adding controller: {}", config.id);
+        self.initialize_controller(config).await
+    }
+
+    pub async fn update_controller(
+        &self,
+        id: &str,
+        config: Pca9685Config,
+    ) -> Result<(), HardwareError> {
+        if config.id != id {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Controller id '{}' does not match '{}'",
+                config.id, id
+            )));
+        }
 
-        // Verify controller exists
-        if !controllers.contains_key(&config.controller_id) {
+        let mut controllers = self.controllers.lock().await;
+        if !controllers.contains_key(id) {
             return Err(HardwareError::NotFound(format!(
                 "Controller '{}' not found",
-                config.controller_id
+                id
             )));
         }
 
-        // Store servo config
+        validate_controller(&controllers, &config)?;
+
+        // Bring up the new driver before dropping the old one so a bad config changes nothing
+        let controller = create_driver(config, self.trace.clone())?;
+
+        // Existing servos must still resolve at the new frequency
+        let servos = self.servos.lock().await;
+        for servo in servos.values().filter(|servo| servo.controller_id == id) {
+            servo.pulse_range(controller.frequency())?;
+        }
+
+        info!("Updating controller: {}", id);
+        controllers.insert(id.to_string(), controller);
+        Ok(())
+    }
+
+    pub async fn remove_controller(&self, id: &str) -> Result<(), HardwareError> {
+        let mut controllers = self.controllers.lock().await;
+        if !controllers.contains_key(id) {
+            return Err(HardwareError::NotFound(format!(
+                "Controller '{}' not found",
+                id
+            )));
+        }
+
+        let servos = self.servos.lock().await;
+        let mut attached: Vec<&str> = servos
+            .values()
+            .filter(|servo| servo.controller_id == id)
+            .map(|servo| servo.name.as_str())
+            .collect();
+        if !attached.is_empty() {
+            attached.sort();
+            return Err(HardwareError::InvalidState(format!(
+                "Controller '{}' still has servos: {}",
+                id,
+                attached.join(", ")
+            )));
+        }
+
+        let claims = self.claims.lock().await;
+        let mut claimed: Vec<&str> = claims
+            .iter()
+            .filter(|((controller_id, _), _)| controller_id == id)
+            .map(|(_, owner)| owner.as_str())
+            .collect();
+        if !claimed.is_empty() {
+            claimed.sort();
+            return Err(HardwareError::InvalidState(format!(
+                "Controller '{}' still has outputs: {}",
+                id,
+                claimed.join(", ")
+            )));
+        }
+
+        info!("Removing controller: {}", id);
+        controllers.remove(id);
+        Ok(())
+    }
+
+    pub async fn add_servo(&self, config: ServoConfig) -> Result<(), HardwareError> {
+        let controllers = self.controllers.lock().await;
         let mut servos = self.servos.lock().await;
+        let claims = self.claims.lock().await;
+        validate_servo(&controllers, &servos, &claims, &config)?;
+
+        // Store servo config
+        let mut states = self.states.lock().await;
+        states.entry(config.name.clone()).or_default();
         servos.insert(config.name.clone(), config);
         Ok(())
     }
 
-    pub async fn move_servo(&self, name: &str, angle: f64) -> Result<(), HardwareError> {
-        // Get servo config
-        let servo_config = {
-            let servos = self.servos.lock().await;
-            servos
+    pub async fn create_servo(&self, config: ServoConfig) -> Result<(), HardwareError> {
+        if self.servos.lock().await.contains_key(&config.name) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Servo '{}' already exists",
+                config.name
+            )));
+        }
+
+        info!("Adding servo: {}", config.name);
+        self.add_servo(config).await
+    }
+
+    pub async fn update_servo(&self, name: &str, config: ServoConfig) -> Result<(), HardwareError> {
+        if config.name != name {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Servo name '{}' does not match '{}'",
+                config.name, name
+            )));
+        }
+
+        let controllers = self.controllers.lock().await;
+        let mut servos = self.servos.lock().await;
+        if !servos.contains_key(name) {
+            return Err(HardwareError::NotFound(format!(
+                "Servo '{}' not found",
+                name
+            )));
+        }
+        let claims = self.claims.lock().await;
+        validate_servo(&controllers, &servos, &claims, &config)?;
+
+        info!("Updating servo: {}", name);
+        servos.insert(name.to_string(), config);
+        Ok(())
+    }
+
+    pub async fn remove_servo(&self, name: &str) -> Result<(), HardwareError> {
+        let mut servos = self.servos.lock().await;
+        if !servos.contains_key(name) {
+            return Err(HardwareError::NotFound(format!(
+                "Servo '{}' not found",
+                name
+            )));
+        }
+
+        let mut states = self.states.lock().await;
+        let groups = self.groups.lock().await;
+        let mut memberships: Vec<&str> = groups
+            .values()
+            .filter(|group| group.servos.iter().any(|servo| servo == name))
+            .map(|group| group.name.as_str())
+            .collect();
+        if !memberships.is_empty() {
+            memberships.sort();
+            return Err(HardwareError::InvalidState(format!(
+                "Servo '{}' is still a member of groups: {}",
+                name,
+                memberships.join(", ")
+            )));
+        }
+
+        let interlocks = self.interlocks.lock().await;
+        if let Some(interlock) = interlocks
+            .iter()
+            .find(|interlock| interlock.servo == name || interlock.requires_servo == name)
+        {
+            return Err(HardwareError::InvalidState(format!(
+                "Servo '{}' is part of interlock '{}'",
+                name, interlock.name
+            )));
+        }
+
+        // Dropping the state also stops any sweep still running on the servo
+        info!("Removing servo: {}", name);
+        servos.remove(name);
+        states.remove(name);
+        if self.leases.lock().await.remove(name).is_some() {
+            self.lease_released.notify_waiters();
+        }
+        Ok(())
+    }
+
+    pub async fn move_servo(&self, name: &str, angle: f64) -> Result<MoveOutcome, HardwareError> {
+        self.move_servo_timed(name, angle, 0, Easing::Linear).await
+    }
+
+    pub async fn move_servo_to(
+        &self,
+        name: &str,
+        target: &ServoTarget,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<MoveOutcome, HardwareError> {
+        let angle = self.servo_config(name).await?.resolve(target)?;
+        self.move_servo_timed(name, angle, duration_ms, easing)
+            .await
+    }
+
+    // Moves to whichever of two named positions the servo is further from. `between` defaults
+    // to the servo's own positions when it has exactly two. Returns the position chosen
+    pub async fn toggle_servo(
+        &self,
+        name: &str,
+        between: Option<[String; 2]>,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<(String, MoveOutcome), HardwareError> {
+        let servo_config = self.servo_config(name).await?;
+
+        let [first, second] = match between {
+            Some(between) => between,
+            None => {
+                let names: Vec<&String> = servo_config.positions.keys().collect();
+                match names.as_slice() {
+                    [first, second] => [first.to_string(), second.to_string()],
+                    _ => {
+                        return Err(HardwareError::InvalidParameter(format!(
+                            "Servo '{}' has {} named positions; name the two to toggle between",
+                            name,
+                            names.len()
+                        )))
+                    }
+                }
+            }
+        };
+        let first_angle = servo_config.resolve(&ServoTarget::Named(first.clone()))?;
+        let second_angle = servo_config.resolve(&ServoTarget::Named(second.clone()))?;
+
+        // Where it was last sent, so toggling twice in a row flips back even mid-move
+        let current = {
+            let states = self.states.lock().await;
+            states
                 .get(name)
-                .cloned()
-                .ok_or_else(|| HardwareError::NotFound(format!("Servo '{}' not found", name)))?
+                .and_then(|state| state.target.or(state.angle))
+        }
+        .ok_or_else(|| {
+            HardwareError::InvalidState(format!(
+                "Servo '{}' has no known position to toggle from",
+                name
+            ))
+        })?;
+
+        let (position, angle) = if (current - first_angle).abs() <= (current - second_angle).abs() {
+            (second, second_angle)
+        } else {
+            (first, first_angle)
         };
 
+        info!("Toggling servo '{}' to '{}'", name, position);
+        let outcome = self
+            .move_servo_timed(name, angle, duration_ms, easing)
+            .await?;
+        Ok((position, outcome))
+    }
+
+    // Looks up named positions so the targets can be handed to move_servos
+    pub async fn resolve_targets(
+        &self,
+        targets: Vec<(String, ServoTarget)>,
+    ) -> Result<Vec<(String, f64)>, HardwareError> {
+        let servos = self.servos.lock().await;
+        targets
+            .into_iter()
+            .map(|(servo, target)| {
+                let servo_config = servos.get(&servo).ok_or_else(|| {
+                    HardwareError::NotFound(format!("Servo '{}' not found", servo))
+                })?;
+                let angle = servo_config.resolve(&target)?;
+                Ok((servo, angle))
+            })
+            .collect()
+    }
+
+    pub async fn move_servo_timed(
+        &self,
+        name: &str,
+        angle: f64,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<MoveOutcome, HardwareError> {
+        self.sequence_interlocks(&[(name.to_string(), angle)], duration_ms, easing)
+            .await?;
+        self.move_single(name, angle, duration_ms, easing).await
+    }
+
+    // Moves one servo, failing if that would break an interlock
+    async fn move_single(
+        &self,
+        name: &str,
+        angle: f64,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<MoveOutcome, HardwareError> {
+        // Get servo config
+        let servo_config = self.servo_config(name).await?;
+
         // Validate angle
-        if angle < servo_config.min_angle || angle > servo_config.max_angle {
+        validate_angle(&servo_config, angle)?;
+
+        if self.calibrations.lock().await.contains_key(name) {
+            return Err(HardwareError::Busy(format!(
+                "Servo '{}' is being calibrated",
+                name
+            )));
+        }
+
+        // Claim the servo, superseding any sweep that is still running
+        let (start_angle, generation) = {
+            let mut states = self.states.lock().await;
+            self.ensure_not_stopped().await?;
+            self.ensure_lease_holder(&[name]).await?;
+            check_interlocks(
+                &self.interlocks.lock().await,
+                &states,
+                &HashMap::from([(name, angle)]),
+            )?;
+            let state = states.entry(name.to_string()).or_default();
+            state.generation += 1;
+            state.target = Some(angle);
+            state.moving = true;
+            state.last_error = None;
+            (state.angle, state.generation)
+        };
+
+        let outcome = plan_move(&servo_config, start_angle, angle, duration_ms, easing);
+
+        let result = self
+            .sweep(
+                name,
+                &servo_config,
+                start_angle,
+                angle,
+                outcome.duration_ms,
+                outcome.easing,
+                generation,
+            )
+            .await;
+
+        // Only the most recent move gets to settle the state
+        let mut states = self.states.lock().await;
+        if let Some(state) = states.get_mut(name) {
+            if state.generation == generation {
+                state.moving = false;
+                if let Err(e) = &result {
+                    state.last_error = Some(e.to_string());
+                }
+            }
+        }
+
+        result.map(|()| outcome)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn sweep(
+        &self,
+        name: &str,
+        servo_config: &ServoConfig,
+        start_angle: Option<f64>,
+        angle: f64,
+        duration_ms: u64,
+        easing: Easing,
+        generation: u64,
+    ) -> Result<(), HardwareError> {
+        // Without a known starting point there is nothing to interpolate from
+        let sweep = match start_angle {
+            Some(start_angle) if duration_ms > 0 => {
+                info!(
+                    "Moving servo '{}' from {} to {} over {}ms ({:?})",
+                    name, start_angle, angle, duration_ms, easing
+                );
+                Sweep::new(start_angle, angle, duration_ms, easing)
+            }
+            // The target is written straight away and held for the planned duration, so the
+            // move only finishes once the servo has had time to get there
+            _ => {
+                info!("Moving servo '{}' to angle {}", name, angle);
+                Sweep::new(angle, angle, duration_ms, Easing::Linear)
+            }
+        };
+
+        let controller = self.controller(&servo_config.controller_id).await?;
+        let pulse_range = servo_config.pulse_range(controller.frequency())?;
+        let key = (servo_config.controller_id.clone(), servo_config.channel);
+        let output = TrackOutput::Servo {
+            config: Box::new(servo_config.clone()),
+            pulse_range,
+            generation,
+        };
+
+        let finished = self
+            .add_tracks(vec![(key, controller, sweep, output)])
+            .await;
+        join_tracks(finished).await
+    }
+
+    // Drives a continuous servo at `speed` (-1.0..=1.0, 0 stops it). With `duration_ms` it
+    // returns to neutral once the time is up
+    pub async fn set_speed(
+        &self,
+        name: &str,
+        speed: f64,
+        duration_ms: Option<u64>,
+    ) -> Result<(), HardwareError> {
+        let servo_config = self.servo_config(name).await?;
+        if servo_config.kind != ServoKind::Continuous {
             return Err(HardwareError::InvalidParameter(format!(
-                "Angle {} is outside valid range [{}, {}]",
-                angle, servo_config.min_angle, servo_config.max_angle
+                "Servo '{}' is positional and is driven by angle, not speed",
+                name
             )));
         }
 
-        // Get controller
-        let controllers = self.controllers.lock().await;
-        let controller = controllers
-            .get(&servo_config.controller_id)
-            .ok_or_else(|| {
+        if !(-1.0..=1.0).contains(&speed) {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Speed {} is outside valid range [-1, 1]",
+                speed
+            )));
+        }
+
+        if self.calibrations.lock().await.contains_key(name) {
+            return Err(HardwareError::Busy(format!(
+                "Servo '{}' is being calibrated",
+                name
+            )));
+        }
+
+        let generation = {
+            let mut states = self.states.lock().await;
+            self.ensure_not_stopped().await?;
+            self.ensure_lease_holder(&[name]).await?;
+            let state = states.entry(name.to_string()).or_default();
+            state.generation += 1;
+            state.moving = speed != 0.0;
+            state.last_error = None;
+            state.generation
+        };
+
+        info!(
+            "Setting servo '{}' speed to {}{}",
+            name,
+            speed,
+            duration_ms
+                .map(|ms| format!(" for {}ms", ms))
+                .unwrap_or_default()
+        );
+        let mut result = self
+            .write_speed(name, &servo_config, speed, generation)
+            .await;
+
+        if let (Ok(()), Some(duration_ms)) = (&result, duration_ms) {
+            tokio::time::sleep(Duration::from_millis(duration_ms)).await;
+            if self.is_superseded(name, generation).await {
+                debug!("Timed run of servo '{}' superseded", name);
+            } else {
+                result = self.write_speed(name, &servo_config, 0.0, generation).await;
+            }
+        }
+
+        let mut states = self.states.lock().await;
+        if let Some(state) = states.get_mut(name) {
+            if state.generation == generation {
+                state.moving = state.speed.is_some_and(|speed| speed != 0.0);
+                if let Err(e) = &result {
+                    state.last_error = Some(e.to_string());
+                }
+            }
+        }
+
+        result
+    }
+
+    async fn write_speed(
+        &self,
+        name: &str,
+        servo_config: &ServoConfig,
+        speed: f64,
+        generation: u64,
+    ) -> Result<(), HardwareError> {
+        let controller = self.controller(&servo_config.controller_id).await?;
+        let pulse_range = servo_config.pulse_range(controller.frequency())?;
+        let neutral = servo_config.neutral_pulse(controller.frequency())?;
+        let pulse_width = speed_to_pulse(servo_config, pulse_range, neutral, speed);
+
+        debug!(
+            "Driving servo '{}' at speed {} (pulse width {})",
+            name, speed, pulse_width
+        );
+        self.write_pulse(name, servo_config, pulse_width, generation)
+            .await?;
+
+        let mut states = self.states.lock().await;
+        if let Some(state) = states
+            .get_mut(name)
+            .filter(|state| state.generation == generation)
+        {
+            state.speed = Some(speed);
+        }
+        Ok(())
+    }
+
+    pub async fn add_group(&self, config: ServoGroupConfig) -> Result<(), HardwareError> {
+        let servos = self.servos.lock().await;
+
+        // Verify every member exists
+        if let Some(missing) = config
+            .servos
+            .iter()
+            .find(|name| !servos.contains_key(*name))
+        {
+            return Err(HardwareError::NotFound(format!(
+                "Servo '{}' in group '{}' not found",
+                missing, config.name
+            )));
+        }
+
+        let mut groups = self.groups.lock().await;
+        groups.insert(config.name.clone(), config);
+        Ok(())
+    }
+
+    pub async fn list_groups(&self) -> HashMap<String, ServoGroupConfig> {
+        let groups = self.groups.lock().await;
+        groups.clone()
+    }
+
+    pub async fn add_interlock(&self, config: InterlockConfig) -> Result<(), HardwareError> {
+        let servos = self.servos.lock().await;
+
+        for servo in [&config.servo, &config.requires_servo] {
+            let servo_config = servos.get(servo).ok_or_else(|| {
                 HardwareError::NotFound(format!(
-                    "Controller '{}' not found",
-                    servo_config.controller_id
+                    "Servo '{}' in interlock '{}' not found",
+                    servo, config.name
                 ))
             })?;
+            if servo_config.kind != ServoKind::Positional {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Interlock '{}' needs positional servos, '{}' is continuous",
+                    config.name, servo
+                )));
+            }
+        }
 
-        // Convert angle to pulse width
-        let pulse_width = self.angle_to_pulse(&servo_config, angle);
-
-        // Get channel
-        let channel = match servo_config.channel {
-            0 => Channel::C0,
-            1 => Channel::C1,
-            2 => Channel::C2,
-            3 => Channel::C3,
-            4 => Channel::C4,
-            5 => Channel::C5,
-            6 => Channel::C6,
-            7 => Channel::C7,
-            8 => Channel::C8,
-            9 => Channel::C9,
-            10 => Channel::C10,
-            11 => Channel::C11,
-            12 => Channel::C12,
-            13 => Channel::C13,
-            14 => Channel::C14,
-            15 => Channel::C15,
-            _ => {
+        if config.servo == config.requires_servo {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Interlock '{}' makes servo '{}' depend on itself",
+                config.name, config.servo
+            )));
+        }
+
+        if let Some([min, max]) = [config.free_range, config.requires_range]
+            .into_iter()
+            .find(|[min, max]| !(min.is_finite() && max.is_finite() && min <= max))
+        {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Interlock '{}' has an invalid range [{}, {}]",
+                config.name, min, max
+            )));
+        }
+
+        // The prerequisite move has to actually satisfy the interlock
+        if let Some(target) = &config.auto_sequence {
+            let angle = servos[&config.requires_servo].resolve(target)?;
+            if !in_range(config.requires_range, angle) {
                 return Err(HardwareError::InvalidParameter(format!(
-                    "Invalid channel number: {}",
-                    servo_config.channel
-                )))
+                    "Interlock '{}' auto-sequences '{}' to {}, outside [{}, {}]",
+                    config.name,
+                    config.requires_servo,
+                    angle,
+                    config.requires_range[0],
+                    config.requires_range[1]
+                )));
+            }
+        }
+
+        let mut interlocks = self.interlocks.lock().await;
+        if interlocks
+            .iter()
+            .any(|interlock| interlock.name == config.name)
+        {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Interlock '{}' already exists",
+                config.name
+            )));
+        }
+        interlocks.push(config);
+        Ok(())
+    }
+
+    pub async fn list_interlocks(&self) -> Vec<InterlockConfig> {
+        self.interlocks.lock().await.clone()
+    }
+
+    // Runs the prerequisite move of every auto-sequenced interlock that `targets` would break.
+    // Anything still in the way is caught when the move claims its servos
+    async fn sequence_interlocks(
+        &self,
+        targets: &[(String, f64)],
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<(), HardwareError> {
+        let interlocks = self.interlocks.lock().await.clone();
+        let planned: HashMap<&str, f64> = targets
+            .iter()
+            .map(|(servo, angle)| (servo.as_str(), *angle))
+            .collect();
+
+        for interlock in &interlocks {
+            let Some(target) = &interlock.auto_sequence else {
+                continue;
+            };
+            // A batch that moves the prerequisite servo itself gets checked as given
+            if !planned.contains_key(interlock.servo.as_str())
+                || planned.contains_key(interlock.requires_servo.as_str())
+            {
+                continue;
+            }
+
+            let violated = {
+                let states = self.states.lock().await;
+                interlock_violation(interlock, &states, &planned).is_some()
+            };
+            if !violated {
+                continue;
             }
+
+            let angle = self
+                .servo_config(&interlock.requires_servo)
+                .await?
+                .resolve(target)?;
+            info!(
+                "Interlock '{}': moving '{}' to {} before '{}'",
+                interlock.name, interlock.requires_servo, angle, interlock.servo
+            );
+            self.move_single(&interlock.requires_servo, angle, duration_ms, easing)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn move_group(
+        &self,
+        name: &str,
+        group_move: GroupMove,
+    ) -> Result<BTreeMap<String, MoveOutcome>, HardwareError> {
+        let group = {
+            let groups = self.groups.lock().await;
+            groups
+                .get(name)
+                .cloned()
+                .ok_or_else(|| HardwareError::NotFound(format!("Group '{}' not found", name)))?
         };
 
-        // Move servo
+        if let Some(unknown) = group_move
+            .overrides
+            .keys()
+            .find(|servo| !group.servos.contains(*servo))
+        {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Servo '{}' is not a member of group '{}'",
+                unknown, name
+            )));
+        }
+
+        let targets = group
+            .servos
+            .iter()
+            .map(|servo| {
+                let target = group_move
+                    .overrides
+                    .get(servo)
+                    .unwrap_or(&group_move.target);
+                (servo.clone(), target.clone())
+            })
+            .collect::<Vec<_>>();
+        let targets = self.resolve_targets(targets).await?;
+
         info!(
-            "Moving servo '{}' to angle {} (pulse width {})",
-            name, angle, pulse_width
+            "Moving group '{}' ({} servos) over {}ms",
+            name,
+            targets.len(),
+            group_move.duration_ms
         );
-        controller.move_servo(channel, pulse_width).await?;
 
-        Ok(())
+        let stagger_ms = group_move.stagger_ms.unwrap_or(group.stagger_ms);
+        self.move_servos(
+            targets,
+            group_move.duration_ms,
+            group_move.easing,
+            stagger_ms,
+        )
+        .await
     }
 
-    fn angle_to_pulse(&self, servo: &ServoConfig, angle: f64) -> u16 {
-        let angle_range = servo.max_angle - servo.min_angle;
-        let pulse_range = servo.max_pulse - servo.min_pulse;
+    pub async fn home_servo(
+        &self,
+        name: &str,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<MoveOutcome, HardwareError> {
+        let home_angle = self.servo_config(name).await?.home_angle.ok_or_else(|| {
+            HardwareError::InvalidParameter(format!("Servo '{}' has no home_angle", name))
+        })?;
 
-        let normalized_angle = angle - servo.min_angle;
-        let pulse_width =
-            servo.min_pulse as f64 + (normalized_angle * pulse_range as f64) / angle_range;
+        info!("Homing servo '{}' to angle {}", name, home_angle);
+        self.move_servo_timed(name, home_angle, duration_ms, easing)
+            .await
+    }
 
-        pulse_width as u16
+    // Sends every servo that has a home_angle to it
+    pub async fn home_all(
+        &self,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<BTreeMap<String, MoveOutcome>, HardwareError> {
+        let targets: Vec<(String, f64)> = {
+            let servos = self.servos.lock().await;
+            servos
+                .values()
+                .filter_map(|servo| {
+                    servo
+                        .home_angle
+                        .map(|home_angle| (servo.name.clone(), home_angle))
+                })
+                .collect()
+        };
+
+        info!("Homing {} servos", targets.len());
+        self.move_servos(targets, duration_ms, easing, 0).await
     }
 
-    pub async fn get_servo_config(&self, name: &str) -> Option<ServoConfig> {
-        let servos = self.servos.lock().await;
-        servos.get(name).cloned()
+    // Moves several servos together, starting each one `stagger_ms` after the previous
+    pub async fn move_servos(
+        &self,
+        targets: Vec<(String, f64)>,
+        duration_ms: u64,
+        easing: Easing,
+        stagger_ms: u64,
+    ) -> Result<BTreeMap<String, MoveOutcome>, HardwareError> {
+        // Without a stagger the servos can move in lock-step
+        if stagger_ms == 0 {
+            return self.move_servos_synced(targets, duration_ms, easing).await;
+        }
+
+        // Validate every target up front so the set never moves halfway
+        for (servo, angle) in &targets {
+            validate_angle(&self.servo_config(servo).await?, *angle)?;
+        }
+        let names: Vec<&str> = targets.iter().map(|(servo, _)| servo.as_str()).collect();
+        self.ensure_lease_holder(&names).await?;
+
+        let owner = current_owner();
+        let mut moves = JoinSet::new();
+        for (index, (servo, angle)) in targets.into_iter().enumerate() {
+            let manager = self.clone();
+            let delay = Duration::from_millis(stagger_ms * index as u64);
+            moves.spawn(run_as(owner.clone(), async move {
+                tokio::time::sleep(delay).await;
+                manager
+                    .move_servo_timed(&servo, angle, duration_ms, easing)
+                    .await
+                    .map(|outcome| (servo.clone(), outcome))
+                    .map_err(|e| format!("{}: {}", servo, e))
+            }));
+        }
+
+        let mut outcomes = BTreeMap::new();
+        let mut failures = Vec::new();
+        while let Some(result) = moves.join_next().await {
+            match result {
+                Ok(Ok((servo, outcome))) => {
+                    outcomes.insert(servo, outcome);
+                }
+                Ok(Err(e)) => failures.push(e),
+                Err(e) => failures.push(e.to_string()),
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(outcomes)
+        } else {
+            error!("Multi-servo move failed: {}", failures.join("; "));
+            Err(HardwareError::Other(format!(
+                "Move failed: {}",
+                failures.join("; ")
+            )))
+        }
     }
 
-    pub async fn list_servos(&self) -> HashMap<String, ServoConfig> {
-        let servos = self.servos.lock().await;
-        servos.clone()
+    // Moves several servos in lock-step. Each step writes all of a controller's channels in one
+    // batch, so the servos start and finish on the same PWM cycle
+    pub async fn move_servos_synced(
+        &self,
+        targets: Vec<(String, f64)>,
+        duration_ms: u64,
+        easing: Easing,
+    ) -> Result<BTreeMap<String, MoveOutcome>, HardwareError> {
+        // Resolve and validate every target up front so the set never moves halfway
+        let mut resolved = Vec::new();
+        for (index, (servo, angle)) in targets.iter().enumerate() {
+            if targets[..index].iter().any(|(other, _)| other == servo) {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Servo '{}' appears more than once in the batch",
+                    servo
+                )));
+            }
+
+            let servo_config = self.servo_config(servo).await?;
+            validate_angle(&servo_config, *angle)?;
+            let controller = self.controller(&servo_config.controller_id).await?;
+            let pulse_range = servo_config.pulse_range(controller.frequency())?;
+            resolved.push((servo_config, controller, pulse_range, *angle));
+        }
+
+        {
+            let calibrations = self.calibrations.lock().await;
+            if let Some((servo_config, ..)) = resolved
+                .iter()
+                .find(|(servo_config, ..)| calibrations.contains_key(&servo_config.name))
+            {
+                return Err(HardwareError::Busy(format!(
+                    "Servo '{}' is being calibrated",
+                    servo_config.name
+                )));
+            }
+        }
+
+        self.sequence_interlocks(&targets, duration_ms, easing)
+            .await?;
+
+        // Claim every servo, superseding any sweeps still running on them
+        let mut moves: Vec<BatchMove> = {
+            let mut states = self.states.lock().await;
+            self.ensure_not_stopped().await?;
+            let names: Vec<&str> = resolved
+                .iter()
+                .map(|(config, ..)| config.name.as_str())
+                .collect();
+            self.ensure_lease_holder(&names).await?;
+            let planned: HashMap<&str, f64> = resolved
+                .iter()
+                .map(|(config, .., angle)| (config.name.as_str(), *angle))
+                .collect();
+            check_interlocks(&self.interlocks.lock().await, &states, &planned)?;
+            resolved
+                .into_iter()
+                .map(|(config, controller, pulse_range, angle)| {
+                    let state = states.entry(config.name.clone()).or_default();
+                    state.generation += 1;
+                    state.target = Some(angle);
+                    state.moving = true;
+                    state.last_error = None;
+
+                    let outcome = plan_move(&config, state.angle, angle, duration_ms, easing);
+                    BatchMove {
+                        start_angle: state.angle.unwrap_or(angle),
+                        generation: state.generation,
+                        config,
+                        controller,
+                        pulse_range,
+                        angle,
+                        outcome,
+                    }
+                })
+                .collect()
+        };
+
+        // The slowest servo sets the pace for the whole batch
+        let duration_ms = moves
+            .iter()
+            .map(|batch_move| batch_move.outcome.duration_ms)
+            .max()
+            .unwrap_or(duration_ms);
+        for batch_move in &mut moves {
+            batch_move.outcome.clamped |= duration_ms > batch_move.outcome.requested_duration_ms;
+            batch_move.outcome.duration_ms = duration_ms;
+        }
+
+        info!(
+            "Moving {} servos together over {}ms",
+            moves.len(),
+            duration_ms
+        );
+        // One shared start keeps the servos in lock-step on every tick
+        let started = Instant::now();
+        let tracks = moves
+            .iter()
+            .map(|batch_move| {
+                let config = &batch_move.config;
+                let sweep = Sweep {
+                    from: batch_move.start_angle,
+                    to: batch_move.angle,
+                    duration_ms,
+                    easing: batch_move.outcome.easing,
+                    started,
+                };
+                let output = TrackOutput::Servo {
+                    config: Box::new(config.clone()),
+                    pulse_range: batch_move.pulse_range,
+                    generation: batch_move.generation,
+                };
+                (
+                    (config.controller_id.clone(), config.channel),
+                    batch_move.controller.clone(),
+                    sweep,
+                    output,
+                )
+            })
+            .collect();
+        let result = join_tracks(self.add_tracks(tracks).await).await;
+
+        // Only servos still owned by this batch get settled
+        let mut states = self.states.lock().await;
+        for batch_move in &moves {
+            if let Some(state) = states.get_mut(&batch_move.config.name) {
+                if state.generation == batch_move.generation {
+                    state.moving = false;
+                    if let Err(e) = &result {
+                        state.last_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+        drop(states);
+
+        result.map(|()| {
+            moves
+                .into_iter()
+                .map(|batch_move| (batch_move.config.name, batch_move.outcome))
+                .collect()
+        })
+    }
+
+    // Scans an I2C bus and matches what answers against the configured controllers
+    pub async fn scan_i2c_bus(&self, bus: &str) -> Result<BusScan, HardwareError> {
+        let bus = i2c_bus_path(bus);
+        let configured: HashMap<u8, String> = {
+            let controllers = self.controllers.lock().await;
+            controllers
+                .values()
+                .map(|controller| controller.get_config())
+                .filter(|config| config.driver == DriverKind::Pca9685 && config.bus_path() == bus)
+                .filter_map(|config| Some((config.address().ok()?, config.id.clone())))
+                .collect()
+        };
+
+        info!("Scanning I2C bus {}", bus);
+        let scan_bus = bus.clone();
+        let found = tokio::task::spawn_blocking(move || probe_bus(&scan_bus))
+            .await
+            .map_err(|e| HardwareError::Other(format!("I2C scan failed: {}", e)))??;
+
+        let devices = found
+            .iter()
+            .map(|&(address, in_use)| ScannedDevice {
+                address: format!("{:#04x}", address),
+                pca9685_range: PCA9685_RANGE.contains(&address),
+                in_use,
+                controller: configured.get(&address).cloned(),
+            })
+            .collect();
+
+        let mut missing_controllers: Vec<String> = configured
+            .into_iter()
+            .filter(|(address, _)| !found.iter().any(|(found, _)| found == address))
+            .map(|(_, id)| id)
+            .collect();
+        missing_controllers.sort();
+
+        Ok(BusScan {
+            bus,
+            devices,
+            missing_controllers,
+        })
+    }
+
+    // Latches the emergency stop, then halts every servo. Motion stays rejected until cleared
+    pub async fn engage_estop(&self, mode: EstopMode) -> Result<(), HardwareError> {
+        *self.estop.lock().await = Some(EmergencyStop {
+            mode,
+            engaged_ms: now_ms(),
+        });
+
+        // Whatever held the servos has been stopped, so nobody owns them any more
+        self.leases.lock().await.clear();
+        self.lease_released.notify_waiters();
+
+        match mode {
+            EstopMode::Relax => self.relax_all().await,
+            EstopMode::Freeze => {
+                // Superseding every sweep leaves each servo on its last written pulse
+                {
+                    let mut states = self.states.lock().await;
+                    for state in states.values_mut() {
+                        state.generation += 1;
+                        state.moving = false;
+                    }
+                }
+
+                // A frozen continuous servo would keep turning, so those stop at neutral
+                let continuous: Vec<(ServoConfig, u64)> = {
+                    let servos = self.servos.lock().await;
+                    let states = self.states.lock().await;
+                    servos
+                        .values()
+                        .filter(|servo| servo.kind == ServoKind::Continuous)
+                        .filter_map(|servo| {
+                            let generation = states.get(&servo.name)?.generation;
+                            Some((servo.clone(), generation))
+                        })
+                        .collect()
+                };
+
+                let mut failures = Vec::new();
+                for (servo, generation) in continuous {
+                    if let Err(e) = self.write_speed(&servo.name, &servo, 0.0, generation).await {
+                        failures.push(format!("{}: {}", servo.name, e));
+                    }
+                }
+
+                if failures.is_empty() {
+                    Ok(())
+                } else {
+                    Err(HardwareError::Other(format!(
+                        "Failed to stop continuous servos: {}",
+                        failures.join("; ")
+                    )))
+                }
+            }
+        }
+    }
+
+    pub async fn clear_estop(&self) -> Result<(), HardwareError> {
+        self.estop
+            .lock()
+            .await
+            .take()
+            .map(|_| ())
+            .ok_or_else(|| HardwareError::InvalidState("Emergency stop is not engaged".to_string()))
+    }
+
+    pub async fn estop_status(&self) -> Option<EmergencyStop> {
+        self.estop.lock().await.clone()
+    }
+
+    async fn ensure_not_stopped(&self) -> Result<(), HardwareError> {
+        match self.estop.lock().await.as_ref() {
+            Some(estop) => Err(HardwareError::InvalidState(format!(
+                "Emergency stop ({:?}) is engaged; clear it before moving servos",
+                estop.mode
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn relax_servo(&self, name: &str) -> Result<(), HardwareError> {
+        let servo_config = self.servo_config(name).await?;
+        info!("Relaxing servo '{}'", name);
+        self.relax(&servo_config, None).await
+    }
+
+    pub async fn relax_group(&self, name: &str) -> Result<(), HardwareError> {
+        let members = {
+            let groups = self.groups.lock().await;
+            groups
+                .get(name)
+                .map(|group| group.servos.clone())
+                .ok_or_else(|| HardwareError::NotFound(format!("Group '{}' not found", name)))?
+        };
+
+        let servos = self.servos.lock().await;
+        let configs = members
+            .iter()
+            .filter_map(|servo| servos.get(servo).cloned())
+            .collect();
+        drop(servos);
+
+        info!("Relaxing group '{}'", name);
+        self.relax_servos(configs).await
+    }
+
+    pub async fn relax_controller(&self, id: &str) -> Result<(), HardwareError> {
+        self.controller(id).await?;
+
+        let servos = self.servos.lock().await;
+        let configs = servos
+            .values()
+            .filter(|servo| servo.controller_id == id)
+            .cloned()
+            .collect();
+        drop(servos);
+
+        info!("Relaxing servos on controller '{}'", id);
+        self.relax_servos(configs).await
+    }
+
+    pub async fn relax_all(&self) -> Result<(), HardwareError> {
+        let configs = self.servos.lock().await.values().cloned().collect();
+        info!("Relaxing all servos");
+        self.relax_servos(configs).await
+    }
+
+    // Relaxes servos once they have been still for their idle_relax_after_ms; runs forever
+    pub async fn run_idle_relax(self) {
+        let mut interval = tokio::time::interval(Duration::from_millis(IDLE_CHECK_INTERVAL_MS));
+
+        loop {
+            interval.tick().await;
+
+            let idle: Vec<(ServoConfig, u64)> = {
+                let servos = self.servos.lock().await;
+                let calibrations = self.calibrations.lock().await;
+                let states = self.states.lock().await;
+                let now = now_ms();
+
+                servos
+                    .values()
+                    .filter(|servo| !calibrations.contains_key(&servo.name))
+                    .filter_map(|servo| {
+                        let idle_after = servo.idle_relax_after_ms?;
+                        let state = states.get(&servo.name)?;
+                        let still_since = state.last_move_ms?;
+                        let is_idle = !state.relaxed
+                            && !state.moving
+                            && now.saturating_sub(still_since) >= idle_after;
+                        is_idle.then(|| (servo.clone(), state.generation))
+                    })
+                    .collect()
+            };
+
+            for (servo_config, generation) in idle {
+                info!("Servo '{}' is idle, relaxing", servo_config.name);
+                if let Err(e) = self.relax(&servo_config, Some(generation)).await {
+                    warn!("Failed to relax idle servo '{}': {}", servo_config.name, e);
+                }
+            }
+        }
+    }
+
+    // Re-initializes controllers that browned out or dropped off the bus
+    pub async fn run_health_checks(self) {
+        let mut interval = tokio::time::interval(Duration::from_millis(HEALTH_CHECK_INTERVAL_MS));
+
+        loop {
+            interval.tick().await;
+
+            let controllers: Vec<Arc<dyn PwmDriver>> =
+                self.controllers.lock().await.values().cloned().collect();
+
+            for controller in controllers {
+                if let Err(e) = controller.check_health().await {
+                    debug!(
+                        "Controller '{}' is still unreachable: {}",
+                        controller.get_config().id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    // Advances every servo move and LED fade at `tick_interval`. Nothing else writes a moving
+    // channel, so this is where limits and interlocks get their last word
+    pub async fn run_motion(self, tick_interval: Duration) {
+        let mut interval = tokio::time::interval(tick_interval);
+        // Trajectories go by the clock, a late tick just lands further along
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+            self.advance_tracks().await;
+        }
+    }
+
+    // Runs `sweep` on a channel through the motion loop, replacing whatever it was doing there
+    pub async fn run_sweep(
+        &self,
+        controller_id: &str,
+        channel: u8,
+        sweep: Sweep,
+        duty: impl Fn(f64) -> Option<u16> + Send + Sync + 'static,
+    ) -> Result<(), HardwareError> {
+        let controller = self.controller(controller_id).await?;
+        let key = (controller_id.to_string(), channel);
+        let output = TrackOutput::Duty(Box::new(duty));
+
+        let finished = self
+            .add_tracks(vec![(key, controller, sweep, output)])
+            .await;
+        join_tracks(finished).await
+    }
+
+    // Hands trajectories to the motion loop in one go, so they all start on the same tick
+    async fn add_tracks(
+        &self,
+        new_tracks: Vec<(ChannelKey, Arc<dyn PwmDriver>, Sweep, TrackOutput)>,
+    ) -> Vec<oneshot::Receiver<Result<(), HardwareError>>> {
+        let states = self.states.lock().await;
+        let mut tracks = self.tracks.lock().await;
+        new_tracks
+            .into_iter()
+            .map(|(key, controller, sweep, output)| {
+                let (done, finished) = oneshot::channel();
+                // A newer command claimed the servo before this one got here, so it must not
+                // replace that command's track
+                if let Some((servo, generation)) = output.claim() {
+                    if states
+                        .get(servo)
+                        .is_none_or(|state| state.generation != generation)
+                    {
+                        debug!("Command for servo '{}' superseded before it started", servo);
+                        let _ = done.send(Ok(()));
+                        return finished;
+                    }
+                }
+
+                let track = Track {
+                    id: self.next_track.fetch_add(1, Ordering::Relaxed),
+                    controller,
+                    sweep,
+                    output,
+                    written: None,
+                    done,
+                };
+                // Replaced mid-flight, which counts as done like any superseded move
+                if let Some(replaced) = tracks.insert(key, track) {
+                    let _ = replaced.done.send(Ok(()));
+                }
+                finished
+            })
+            .collect()
+    }
+
+    // One tick: samples every trajectory, checks servo angles against their limits and the
+    // interlocks, then writes the channels that changed, one transaction per controller. The
+    // tracks are only locked while sampling and again while the results are applied, so a new
+    // command never waits on a slow board. Every servo, LED and output write goes through here
+    // and ticks never overlap, so a write queued mid-tick still lands after the one in flight
+    async fn advance_tracks(&self) {
+        let now = Instant::now();
+        let mut samples = Vec::new();
+        let mut batches: HashMap<String, ControllerWrites> = HashMap::new();
+
+        {
+            let states = self.states.lock().await;
+            let interlocks = self.interlocks.lock().await;
+            let mut tracks = self.tracks.lock().await;
+            if tracks.is_empty() {
+                return;
+            }
+
+            let keys: Vec<ChannelKey> = tracks.keys().cloned().collect();
+            for key in keys {
+                let track = &tracks[&key];
+                let value = track.sweep.value_at(now);
+
+                let superseded = track.output.claim().filter(|(servo, generation)| {
+                    states
+                        .get(*servo)
+                        .is_none_or(|state| state.generation != *generation)
+                });
+                let output = match (&track.output, superseded) {
+                    (_, Some((servo, _))) => {
+                        debug!("Command for servo '{}' superseded by a newer one", servo);
+                        Err(None)
+                    }
+                    (
+                        TrackOutput::Servo {
+                            config,
+                            pulse_range,
+                            ..
+                        },
+                        None,
+                    ) => validate_angle(config, value)
+                        .and_then(|()| {
+                            let planned = HashMap::from([(config.name.as_str(), value)]);
+                            check_interlocks(&interlocks, &states, &planned)
+                        })
+                        .map(|()| Some(self.angle_to_pulse(config, *pulse_range, value)))
+                        .map_err(Some),
+                    (TrackOutput::Fixed { output, .. }, None) => Ok(*output),
+                    (TrackOutput::Duty(duty), None) => Ok(duty(value)),
+                };
+
+                let output = match output {
+                    Ok(output) => output,
+                    Err(error) => {
+                        if let Some(track) = tracks.remove(&key) {
+                            let _ = track.done.send(error.map_or(Ok(()), Err));
+                        }
+                        continue;
+                    }
+                };
+
+                let changed = track.written != Some(output);
+                if changed {
+                    let batch = batches
+                        .entry(key.0.clone())
+                        .or_insert_with(|| ControllerWrites {
+                            controller: track.controller.clone(),
+                            pulses: Vec::new(),
+                            full_on: Vec::new(),
+                            full_off: Vec::new(),
+                        });
+                    match output {
+                        Some(pulse) if pulse as f64 >= PWM_RESOLUTION => batch.full_on.push(key.1),
+                        Some(pulse) => batch.pulses.push((key.1, pulse)),
+                        None => batch.full_off.push(key.1),
+                    }
+                }
+                samples.push(Sample {
+                    id: track.id,
+                    value,
+                    output,
+                    changed,
+                    finished: track.sweep.is_finished(now),
+                    key,
+                });
+            }
+        }
+
+        let mut writes = JoinSet::new();
+        for (controller_id, batch) in batches {
+            writes.spawn(async move {
+                let controller = batch.controller;
+                let mut result = if batch.pulses.is_empty() {
+                    Ok(())
+                } else {
+                    controller.set_pulses(&batch.pulses).await
+                };
+                for channel in batch.full_on {
+                    if result.is_ok() {
+                        result = controller.set_full_on(channel).await;
+                    }
+                }
+                for channel in batch.full_off {
+                    if result.is_ok() {
+                        result = controller.set_full_off(channel).await;
+                    }
+                }
+                (controller_id, result)
+            });
+        }
+        let mut failures = HashMap::new();
+        while let Some(joined) = writes.join_next().await {
+            match joined {
+                Ok((controller_id, Err(e))) => {
+                    failures.insert(controller_id, e);
+                }
+                Ok(_) => {}
+                Err(e) => error!("Motion write task failed: {}", e),
+            }
+        }
+
+        let mut written = Vec::new();
+        let mut finished = Vec::new();
+        let mut tracks = self.tracks.lock().await;
+        for sample in samples {
+            // Replaced while the writes were out; the new track starts from scratch
+            let track = match tracks.get_mut(&sample.key) {
+                Some(track) if track.id == sample.id => track,
+                _ => continue,
+            };
+
+            if let Some(error) = failures.get(&sample.key.0).filter(|_| sample.changed) {
+                if let Some(track) = tracks.remove(&sample.key) {
+                    let _ = track.done.send(Err(duplicate(error)));
+                }
+                continue;
+            }
+
+            track.written = Some(sample.output);
+            if let TrackOutput::Servo {
+                config, generation, ..
+            } = &track.output
+            {
+                written.push((
+                    config.name.clone(),
+                    *generation,
+                    sample.value,
+                    sample.output,
+                ));
+            }
+
+            if sample.finished {
+                if let Some(track) = tracks.remove(&sample.key) {
+                    finished.push(track.done);
+                }
+            }
+        }
+        drop(tracks);
+
+        // A command that claimed the servo during the writes has moved the generation on
+        let changed_ms = now_ms();
+        let mut states = self.states.lock().await;
+        for (name, generation, angle, pulse) in written {
+            if let Some(state) = states
+                .get_mut(&name)
+                .filter(|state| state.generation == generation)
+            {
+                state.angle = Some(angle);
+                state.pulse = pulse;
+                state.relaxed = false;
+                state.last_move_ms = Some(changed_ms);
+            }
+        }
+        drop(states);
+
+        // Waiters only hear back once the state they'll read next is up to date
+        for done in finished {
+            let _ = done.send(Ok(()));
+        }
+    }
+
+    async fn relax_servos(&self, configs: Vec<ServoConfig>) -> Result<(), HardwareError> {
+        let mut failures = Vec::new();
+        for servo_config in configs {
+            if let Err(e) = self.relax(&servo_config, None).await {
+                failures.push(format!("{}: {}", servo_config.name, e));
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            error!("Relax failed: {}", failures.join("; "));
+            Err(HardwareError::Other(format!(
+                "Relax failed: {}",
+                failures.join("; ")
+            )))
+        }
+    }
+
+    // Turns the servo's channel fully off. With `idle_generation` set, nothing happens if a
+    // move has started since the servo was found idle
+    async fn relax(
+        &self,
+        servo_config: &ServoConfig,
+        idle_generation: Option<u64>,
+    ) -> Result<(), HardwareError> {
+        // Idle relax only acts on servos nobody has moved since, leased or not
+        if idle_generation.is_none() {
+            self.ensure_lease_holder(&[&servo_config.name]).await?;
+        }
+
+        let generation = {
+            let mut states = self.states.lock().await;
+            let state = states.get_mut(&servo_config.name).ok_or_else(|| {
+                HardwareError::NotFound(format!("Servo '{}' not found", servo_config.name))
+            })?;
+            match idle_generation {
+                Some(generation) if generation != state.generation => return Ok(()),
+                Some(_) => {}
+                // Stop any sweep in flight
+                None => state.generation += 1,
+            }
+            state.moving = false;
+            state.generation
+        };
+
+        // Replaces the servo's track, and lands after any pulse of it still on its way out
+        self.write_servo(servo_config, None, generation).await?;
+
+        let mut states = self.states.lock().await;
+        if let Some(state) = states
+            .get_mut(&servo_config.name)
+            .filter(|state| state.generation == generation)
+        {
+            state.relaxed = true;
+            if state.speed.is_some() {
+                state.speed = Some(0.0);
+            }
+        }
+        Ok(())
+    }
+
+    // Gives `request.owner` the servos. Only the owner may move them until it releases them or an
+    // emergency stop revokes every lease
+    pub async fn acquire_lease(&self, request: LeaseRequest) -> Result<(), HardwareError> {
+        for servo in &request.servos {
+            self.servo_config(servo).await?;
+        }
+
+        let deadline = request
+            .timeout_ms
+            .map(|ms| tokio::time::Instant::now() + Duration::from_millis(ms));
+
+        loop {
+            // Listen before checking, so a release in between isn't missed
+            let released = self.lease_released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            let conflicts = {
+                let mut states = self.states.lock().await;
+                let mut leases = self.leases.lock().await;
+
+                let conflicts: Vec<(String, Lease)> = request
+                    .servos
+                    .iter()
+                    .filter_map(|servo| {
+                        let lease = leases.get(servo)?;
+                        (lease.owner != request.owner).then(|| (servo.clone(), lease.clone()))
+                    })
+                    .collect();
+
+                let preempt = request.on_conflict == ConflictPolicy::Preempt
+                    && conflicts
+                        .iter()
+                        .all(|(_, lease)| lease.priority < request.priority);
+
+                if conflicts.is_empty() || preempt {
+                    for (servo, lease) in &conflicts {
+                        warn!(
+                            "'{}' (priority {}) preempted '{}' (priority {}) on servo '{}'",
+                            request.owner, request.priority, lease.owner, lease.priority, servo
+                        );
+                        // Stops whatever the previous owner still has running on it
+                        if let Some(state) = states.get_mut(servo) {
+                            state.generation += 1;
+                            state.moving = false;
+                        }
+                    }
+
+                    let lease = Lease {
+                        owner: request.owner.clone(),
+                        priority: request.priority,
+                        acquired_ms: now_ms(),
+                    };
+                    for servo in &request.servos {
+                        leases.insert(servo.clone(), lease.clone());
+                    }
+                    info!(
+                        "'{}' leased servos: {}",
+                        request.owner,
+                        request.servos.join(", ")
+                    );
+                    return Ok(());
+                }
+
+                conflicts
+            };
+
+            let held_by = describe_leases(&conflicts);
+            match request.on_conflict {
+                ConflictPolicy::Reject => {
+                    return Err(HardwareError::Busy(format!(
+                        "Servos are leased: {}",
+                        held_by
+                    )))
+                }
+                ConflictPolicy::Preempt => {
+                    return Err(HardwareError::Busy(format!(
+                        "Priority {} is not high enough to preempt {}",
+                        request.priority, held_by
+                    )))
+                }
+                ConflictPolicy::Queue => {
+                    debug!("'{}' waiting for servos: {}", request.owner, held_by);
+                    match deadline {
+                        Some(deadline) => {
+                            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                                return Err(HardwareError::Timeout(format!(
+                                    "Servos are still leased: {}",
+                                    held_by
+                                )));
+                            }
+                        }
+                        None => released.await,
+                    }
+                }
+            }
+        }
+    }
+
+    // Releases every servo leased to `owner`
+    pub async fn release_lease(&self, owner: &str) -> Result<(), HardwareError> {
+        let mut leases = self.leases.lock().await;
+        let before = leases.len();
+        leases.retain(|_, lease| lease.owner != owner);
+        if leases.len() == before {
+            return Err(HardwareError::NotFound(format!(
+                "'{}' holds no leases",
+                owner
+            )));
+        }
+
+        info!("'{}' released its servos", owner);
+        self.lease_released.notify_waiters();
+        Ok(())
+    }
+
+    pub async fn list_leases(&self) -> BTreeMap<String, Lease> {
+        let leases = self.leases.lock().await;
+        leases
+            .iter()
+            .map(|(servo, lease)| (servo.clone(), lease.clone()))
+            .collect()
+    }
+
+    // Fails, naming the owner, if any of the servos is leased to someone other than the caller
+    async fn ensure_lease_holder(&self, servos: &[&str]) -> Result<(), HardwareError> {
+        let owner = current_owner();
+        let leases = self.leases.lock().await;
+        for servo in servos {
+            if let Some(lease) = leases.get(*servo) {
+                if owner.as_ref().is_none_or(|owner| owner.name != lease.owner) {
+                    return Err(HardwareError::Busy(format!(
+                        "Servo '{}' is leased by '{}' (priority {})",
+                        servo, lease.owner, lease.priority
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Reserves a channel for an output that isn't a servo, such as an LED, so nothing else
+    // can be wired to it
+    pub async fn claim_channel(
+        &self,
+        controller_id: &str,
+        channel: u8,
+        owner: &str,
+    ) -> Result<(), HardwareError> {
+        self.controller(controller_id).await?;
+        if channel > 15 {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Invalid channel number: {}",
+                channel
+            )));
+        }
+
+        let servos = self.servos.lock().await;
+        let mut claims = self.claims.lock().await;
+        if let Some(servo) = servos
+            .values()
+            .find(|servo| servo.controller_id == controller_id && servo.channel == channel)
+        {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Channel {} on controller '{}' is already used by servo '{}'",
+                channel, controller_id, servo.name
+            )));
+        }
+
+        let key = (controller_id.to_string(), channel);
+        match claims.get(&key) {
+            Some(other) if other != owner => Err(HardwareError::InvalidParameter(format!(
+                "Channel {} on controller '{}' is already used by '{}'",
+                channel, controller_id, other
+            ))),
+            _ => {
+                claims.insert(key, owner.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn controller(&self, id: &str) -> Result<Arc<dyn PwmDriver>, HardwareError> {
+        let controllers = self.controllers.lock().await;
+        controllers
+            .get(id)
+            .cloned()
+            .ok_or_else(|| HardwareError::NotFound(format!("Controller '{}' not found", id)))
+    }
+
+    pub async fn simulated_board(&self, id: &str) -> Result<SimulatedBoard, HardwareError> {
+        self.controller(id).await?.simulated().ok_or_else(|| {
+            HardwareError::InvalidParameter(format!("Controller '{}' is not simulated", id))
+        })
+    }
+
+    pub fn trace(&self) -> &TraceRecorder {
+        &self.trace
+    }
+
+    async fn servo_config(&self, name: &str) -> Result<ServoConfig, HardwareError> {
+        self.get_servo_config(name)
+            .await
+            .ok_or_else(|| HardwareError::NotFound(format!("Servo '{}' not found", name)))
+    }
+
+    async fn is_superseded(&self, name: &str, generation: u64) -> bool {
+        let states = self.states.lock().await;
+        states
+            .get(name)
+            .is_none_or(|state| state.generation != generation)
+    }
+
+    // Holds the servo on a raw pulse, outside of any angle
+    async fn write_pulse(
+        &self,
+        name: &str,
+        servo_config: &ServoConfig,
+        pulse_width: u16,
+        generation: u64,
+    ) -> Result<(), HardwareError> {
+        self.write_servo(servo_config, Some(pulse_width), generation)
+            .await?;
+
+        let mut states = self.states.lock().await;
+        if let Some(state) = states
+            .get_mut(name)
+            .filter(|state| state.generation == generation)
+        {
+            state.angle = None;
+            state.pulse = Some(pulse_width);
+            state.relaxed = false;
+            state.last_move_ms = Some(now_ms());
+        }
+
+        Ok(())
+    }
+
+    // Queues one output for the servo on the motion loop and waits until it is written. Returns
+    // without writing if a newer command claims the servo first
+    async fn write_servo(
+        &self,
+        servo_config: &ServoConfig,
+        output: Option<u16>,
+        generation: u64,
+    ) -> Result<(), HardwareError> {
+        let controller = self.controller(&servo_config.controller_id).await?;
+        let key = (servo_config.controller_id.clone(), servo_config.channel);
+        let output = TrackOutput::Fixed {
+            servo: servo_config.name.clone(),
+            generation,
+            output,
+        };
+
+        let finished = self
+            .add_tracks(vec![(key, controller, Sweep::hold(0.0), output)])
+            .await;
+        join_tracks(finished).await
+    }
+
+    pub async fn start_calibration(&self, name: &str) -> Result<CalibrationSession, HardwareError> {
+        self.servo_config(name).await?;
+        self.ensure_lease_holder(&[name]).await?;
+
+        // Stop any sweep in flight; calibration owns the servo from here on
+        let pulse = {
+            let mut states = self.states.lock().await;
+            states.get_mut(name).and_then(|state| {
+                state.generation += 1;
+                state.moving = false;
+                state.pulse
+            })
+        };
+
+        let mut calibrations = self.calibrations.lock().await;
+        let session = calibrations
+            .entry(name.to_string())
+            .or_insert_with(|| CalibrationSession {
+                pulse,
+                ..Default::default()
+            });
+
+        info!("Started calibration of servo '{}'", name);
+        Ok(session.clone())
+    }
+
+    pub async fn get_calibration(&self, name: &str) -> Result<CalibrationSession, HardwareError> {
+        let calibrations = self.calibrations.lock().await;
+        calibrations
+            .get(name)
+            .cloned()
+            .ok_or_else(|| not_calibrating(name))
+    }
+
+    // Drives a raw pulse, bypassing the angle limits
+    pub async fn set_calibration_pulse(
+        &self,
+        name: &str,
+        pulse_width: u16,
+    ) -> Result<CalibrationSession, HardwareError> {
+        if pulse_width as f64 >= PWM_RESOLUTION {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Pulse width {} is outside the 12-bit range",
+                pulse_width
+            )));
+        }
+        self.ensure_not_stopped().await?;
+        self.ensure_lease_holder(&[name]).await?;
+
+        let servo_config = self.servo_config(name).await?;
+        self.get_calibration(name).await?;
+
+        // Each pulse replaces the last one, even if that hasn't gone out yet
+        let generation = {
+            let mut states = self.states.lock().await;
+            let state = states.entry(name.to_string()).or_default();
+            state.generation += 1;
+            state.generation
+        };
+
+        info!(
+            "Calibrating servo '{}' at pulse width {}",
+            name, pulse_width
+        );
+        self.write_pulse(name, &servo_config, pulse_width, generation)
+            .await?;
+
+        let mut calibrations = self.calibrations.lock().await;
+        let session = calibrations
+            .get_mut(name)
+            .ok_or_else(|| not_calibrating(name))?;
+        session.pulse = Some(pulse_width);
+        Ok(session.clone())
+    }
+
+    pub async fn nudge_calibration(
+        &self,
+        name: &str,
+        delta: i32,
+    ) -> Result<CalibrationSession, HardwareError> {
+        let current = self.get_calibration(name).await?.pulse.ok_or_else(|| {
+            HardwareError::InvalidState(format!(
+                "Servo '{}' has no pulse to nudge from; set one first",
+                name
+            ))
+        })?;
+
+        let pulse_width = (current as i32 + delta).clamp(0, PWM_RESOLUTION as i32 - 1) as u16;
+        self.set_calibration_pulse(name, pulse_width).await
+    }
+
+    pub async fn mark_calibration(
+        &self,
+        name: &str,
+        mark: CalibrationMark,
+    ) -> Result<CalibrationSession, HardwareError> {
+        let mut calibrations = self.calibrations.lock().await;
+        let session = calibrations
+            .get_mut(name)
+            .ok_or_else(|| not_calibrating(name))?;
+
+        let pulse = session.pulse.ok_or_else(|| {
+            HardwareError::InvalidState(format!("Servo '{}' has no pulse to mark yet", name))
+        })?;
+
+        info!(
+            "Marked pulse width {} as {:?} for servo '{}'",
+            pulse, mark, name
+        );
+        session.mark(mark, pulse);
+        Ok(session.clone())
+    }
+
+    // Writes the marked pulses into the servo's config and ends the session
+    // With `swap_ends`, a min mark above the max mark is taken to mean the servo is mounted
+    // mirrored: the ends are swapped and `inverted` is flipped. Without it that is an error
+    pub async fn commit_calibration(
+        &self,
+        name: &str,
+        swap_ends: bool,
+    ) -> Result<ServoConfig, HardwareError> {
+        let controllers = self.controllers.lock().await;
+        let mut servos = self.servos.lock().await;
+        let mut calibrations = self.calibrations.lock().await;
+        let claims = self.claims.lock().await;
+
+        let session = calibrations
+            .get(name)
+            .ok_or_else(|| not_calibrating(name))?;
+        let mut config = servos
+            .get(name)
+            .cloned()
+            .ok_or_else(|| HardwareError::NotFound(format!("Servo '{}' not found", name)))?;
+        let controller = controllers.get(&config.controller_id).ok_or_else(|| {
+            HardwareError::NotFound(format!("Controller '{}' not found", config.controller_id))
+        })?;
+
+        // Unmarked ends keep their current values
+        let (current_min, current_max) = config
+            .pulse_range(controller.frequency())
+            .map(|(min, max)| (Some(min), Some(max)))
+            .unwrap_or((None, None));
+        let mut min_pulse = session.min_pulse.or(current_min);
+        let mut max_pulse = session.max_pulse.or(current_max);
+
+        if let (Some(min), Some(max)) = (min_pulse, max_pulse) {
+            if min > max {
+                if !swap_ends {
+                    return Err(HardwareError::InvalidParameter(format!(
+                        "Min pulse {} is above max pulse {}; re-mark the ends, or commit with \
+                         swap_ends to swap them and flip `inverted`",
+                        min, max
+                    )));
+                }
+                min_pulse = Some(max);
+                max_pulse = Some(min);
+                config.inverted = !config.inverted;
+            }
+        }
+
+        config.min_pulse = min_pulse;
+        config.max_pulse = max_pulse;
+        config.min_pulse_us = None;
+        config.max_pulse_us = None;
+
+        // On a continuous servo the home mark is where it stands still
+        if config.kind == ServoKind::Continuous {
+            if let Some(home) = session.home_pulse {
+                config.neutral_pulse = Some(home);
+                config.neutral_pulse_us = None;
+            }
+        }
+        validate_servo(&controllers, &servos, &claims, &config)?;
+
+        if let (ServoKind::Positional, Some(home), Some(min), Some(max)) =
+            (config.kind, session.home_pulse, min_pulse, max_pulse)
+        {
+            let home_angle = self.pulse_to_angle(&config, (min, max), home);
+            config.home_angle = Some((home_angle * 100.0).round() / 100.0);
+        }
+
+        // The held pulse now stands for a different angle
+        if let (ServoKind::Positional, Some(min), Some(max)) = (config.kind, min_pulse, max_pulse) {
+            let mut states = self.states.lock().await;
+            if let Some(state) = states.get_mut(name) {
+                if let Some(pulse) = state.pulse.filter(|_| !state.relaxed) {
+                    state.angle = Some(self.pulse_to_angle(&config, (min, max), pulse));
+                }
+            }
+        }
+
+        info!(
+            "Committed calibration of servo '{}': pulse range [{:?}, {:?}], home {:?}, inverted {}",
+            name, config.min_pulse, config.max_pulse, config.home_angle, config.inverted
+        );
+        servos.insert(name.to_string(), config.clone());
+        calibrations.remove(name);
+        Ok(config)
+    }
+
+    pub async fn cancel_calibration(&self, name: &str) -> Result<(), HardwareError> {
+        let mut calibrations = self.calibrations.lock().await;
+        calibrations
+            .remove(name)
+            .map(|_| info!("Cancelled calibration of servo '{}'", name))
+            .ok_or_else(|| not_calibrating(name))
+    }
+
+    fn angle_to_pulse(
+        &self,
+        servo: &ServoConfig,
+        (min_pulse, max_pulse): (u16, u16),
+        angle: f64,
+    ) -> u16 {
+        let angle_range = servo.max_angle - servo.min_angle;
+        let pulse_range = max_pulse - min_pulse;
+
+        let physical_angle = if servo.inverted {
+            servo.min_angle + servo.max_angle - angle
+        } else {
+            angle
+        } + servo.trim_degrees;
+
+        // Trim must never push the pulse past the calibrated end stops
+        let normalized_angle = (physical_angle - servo.min_angle).clamp(0.0, angle_range);
+        let pulse_width = min_pulse as f64 + (normalized_angle * pulse_range as f64) / angle_range;
+
+        pulse_width as u16
+    }
+
+    fn pulse_to_angle(
+        &self,
+        servo: &ServoConfig,
+        (min_pulse, max_pulse): (u16, u16),
+        pulse_width: u16,
+    ) -> f64 {
+        let angle_range = servo.max_angle - servo.min_angle;
+        let fraction = (pulse_width as f64 - min_pulse as f64) / (max_pulse - min_pulse) as f64;
+
+        let physical_angle =
+            servo.min_angle + fraction.clamp(0.0, 1.0) * angle_range - servo.trim_degrees;
+        let angle = if servo.inverted {
+            servo.min_angle + servo.max_angle - physical_angle
+        } else {
+            physical_angle
+        };
+
+        angle.clamp(servo.min_angle, servo.max_angle)
+    }
+
+    pub async fn get_servo_config(&self, name: &str) -> Option<ServoConfig> {
+        let servos = self.servos.lock().await;
+        servos.get(name).cloned()
+    }
+
+    pub async fn get_servo(&self, name: &str) -> Result<ServoStatus, HardwareError> {
+        let servos = self.servos.lock().await;
+        let config = servos
+            .get(name)
+            .cloned()
+            .ok_or_else(|| HardwareError::NotFound(format!("Servo '{}' not found", name)))?;
+
+        let states = self.states.lock().await;
+        let state = states.get(name).cloned().unwrap_or_default();
+        let lease = self.leases.lock().await.get(name).cloned();
+        Ok(ServoStatus {
+            config,
+            state,
+            lease,
+        })
+    }
+
+    pub async fn list_servos(&self) -> HashMap<String, ServoStatus> {
+        let servos = self.servos.lock().await;
+        let states = self.states.lock().await;
+        let leases = self.leases.lock().await;
+        servos
+            .iter()
+            .map(|(name, config)| {
+                let state = states.get(name).cloned().unwrap_or_default();
+                (
+                    name.clone(),
+                    ServoStatus {
+                        config: config.clone(),
+                        state,
+                        lease: leases.get(name).cloned(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+fn not_calibrating(name: &str) -> HardwareError {
+    HardwareError::InvalidState(format!("Servo '{}' is not being calibrated", name))
+}
+
+fn validate_angle(servo_config: &ServoConfig, angle: f64) -> Result<(), HardwareError> {
+    if servo_config.kind == ServoKind::Continuous {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Servo '{}' is continuous and is driven by speed, not angle",
+            servo_config.name
+        )));
+    }
+
+    if angle < servo_config.min_angle || angle > servo_config.max_angle {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Angle {} is outside valid range [{}, {}]",
+            angle, servo_config.min_angle, servo_config.max_angle
+        )));
+    }
+    Ok(())
+}
+
+// Maps speed in -1.0..=1.0 onto the pulse range, skipping over the deadband around neutral
+fn speed_to_pulse(
+    servo_config: &ServoConfig,
+    (min_pulse, max_pulse): (u16, u16),
+    neutral: u16,
+    speed: f64,
+) -> u16 {
+    let speed = if servo_config.inverted { -speed } else { speed };
+    if speed == 0.0 {
+        return neutral;
+    }
+
+    let edge = if speed > 0.0 { max_pulse } else { min_pulse };
+    let span = (edge as f64 - neutral as f64).abs();
+    let deadband = (servo_config.deadband as f64).min(span);
+    let offset = deadband + speed.abs() * (span - deadband);
+
+    (neutral as f64 + offset.copysign(speed)).round() as u16
+}
+
+// Applies the servo's speed and acceleration limits to a move starting at `start_angle`
+fn plan_move(
+    servo_config: &ServoConfig,
+    start_angle: Option<f64>,
+    angle: f64,
+    duration_ms: u64,
+    easing: Easing,
+) -> MoveOutcome {
+    let outcome = match start_angle {
+        Some(start_angle) => limit_move(servo_config, angle - start_angle, duration_ms, easing),
+        // The servo may be anywhere, so allow for a sweep across its whole range
+        None => {
+            let travel = servo_config.max_angle - servo_config.min_angle;
+            let outcome = limit_move(servo_config, travel, duration_ms, easing);
+            if outcome.clamped {
+                warn!(
+                    "Servo '{}' position is unknown, allowing {}ms for a full sweep",
+                    servo_config.name, outcome.duration_ms
+                );
+            }
+            outcome
+        }
+    };
+
+    if outcome.clamped {
+        info!(
+            "Move of servo '{}' stretched from {}ms to {}ms to respect its limits",
+            servo_config.name, outcome.requested_duration_ms, outcome.duration_ms
+        );
+    }
+    outcome
+}
+
+fn validate_controller(
+    controllers: &HashMap<String, Arc<dyn PwmDriver>>,
+    config: &Pca9685Config,
+) -> Result<(), HardwareError> {
+    let address = config.address()?;
+    let bus = config.bus_path();
+
+    // Two boards can't answer on the same address of the same bus
+    for other in controllers
+        .values()
+        .map(|controller| controller.get_config())
+    {
+        if other.id != config.id && other.bus_path() == bus && other.address()? == address {
+            return Err(HardwareError::InvalidParameter(format!(
+                "Address {:#04x} on {} is already used by controller '{}'",
+                address, bus, other.id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_servo(
+    controllers: &HashMap<String, Arc<dyn PwmDriver>>,
+    servos: &HashMap<String, ServoConfig>,
+    claims: &HashMap<(String, u8), String>,
+    config: &ServoConfig,
+) -> Result<(), HardwareError> {
+    // Verify controller exists
+    let controller = controllers.get(&config.controller_id).ok_or_else(|| {
+        HardwareError::NotFound(format!("Controller '{}' not found", config.controller_id))
+    })?;
+
+    if config.channel > 15 {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Invalid channel number: {}",
+            config.channel
+        )));
+    }
+
+    match config.kind {
+        ServoKind::Positional => {
+            if config.min_angle >= config.max_angle {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Servo '{}' has an invalid angle range [{}, {}]",
+                    config.name, config.min_angle, config.max_angle
+                )));
+            }
+
+            if let Some(home_angle) = config.home_angle {
+                validate_angle(config, home_angle)?;
+            }
+
+            if let Some((position, angle)) = config
+                .positions
+                .iter()
+                .find(|(_, angle)| validate_angle(config, **angle).is_err())
+            {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Servo '{}' position '{}' at {} is outside valid range [{}, {}]",
+                    config.name, position, angle, config.min_angle, config.max_angle
+                )));
+            }
+
+            if config.trim_degrees.abs() >= config.max_angle - config.min_angle {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Servo '{}' trim of {} degrees exceeds its angle range",
+                    config.name, config.trim_degrees
+                )));
+            }
+        }
+        ServoKind::Continuous => {
+            if config.home_angle.is_some() || !config.positions.is_empty() {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Servo '{}' is continuous and can't have a home_angle or named positions",
+                    config.name
+                )));
+            }
+
+            // Full speed in either direction has to lie outside the deadband
+            let (min_pulse, max_pulse) = config.pulse_range(controller.frequency())?;
+            let neutral = config.neutral_pulse(controller.frequency())?;
+            if config.deadband >= (neutral - min_pulse).min(max_pulse - neutral) {
+                return Err(HardwareError::InvalidParameter(format!(
+                    "Servo '{}' deadband of {} ticks leaves no speed range around neutral {}",
+                    config.name, config.deadband, neutral
+                )));
+            }
+        }
+    }
+
+    let limits = [
+        ("max_speed_deg_per_s", config.max_speed_deg_per_s),
+        ("max_accel", config.max_accel),
+    ];
+    if let Some((field, limit)) = limits
+        .iter()
+        .find(|(_, limit)| limit.is_some_and(|limit| limit <= 0.0 || !limit.is_finite()))
+    {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Servo '{}' has an invalid {} of {}",
+            config.name,
+            field,
+            limit.unwrap_or_default()
+        )));
+    }
+
+    // Verify the pulse range resolves at the controller's real frequency
+    let (min_pulse, max_pulse) = config.pulse_range(controller.frequency())?;
+    info!(
+        "Servo '{}' pulse range [{}, {}] ticks",
+        config.name, min_pulse, max_pulse
+    );
+
+    // Two servos can't share a channel
+    if let Some(other) = servos.values().find(|other| {
+        other.name != config.name
+            && other.controller_id == config.controller_id
+            && other.channel == config.channel
+    }) {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Channel {} on controller '{}' is already used by servo '{}'",
+            config.channel, config.controller_id, other.name
+        )));
+    }
+
+    if let Some(owner) = claims.get(&(config.controller_id.clone(), config.channel)) {
+        return Err(HardwareError::InvalidParameter(format!(
+            "Channel {} on controller '{}' is already used by '{}'",
+            config.channel, config.controller_id, owner
+        )));
+    }
+
+    Ok(())
+}
+
+fn describe_leases(conflicts: &[(String, Lease)]) -> String {
+    conflicts
+        .iter()
+        .map(|(servo, lease)| {
+            format!(
+                "'{}' by '{}' (priority {})",
+                servo, lease.owner, lease.priority
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Fails with the first interlock the planned moves would break. Interlocks that none of the
+// planned servos take part in are left alone
+fn check_interlocks(
+    interlocks: &[InterlockConfig],
+    states: &HashMap<String, ServoState>,
+    planned: &HashMap<&str, f64>,
+) -> Result<(), HardwareError> {
+    for interlock in interlocks {
+        if !planned.contains_key(interlock.servo.as_str())
+            && !planned.contains_key(interlock.requires_servo.as_str())
+        {
+            continue;
+        }
+        if let Some(violation) = interlock_violation(interlock, states, planned) {
+            return Err(HardwareError::InvalidState(violation));
+        }
+    }
+    Ok(())
+}
+
+fn interlock_violation(
+    interlock: &InterlockConfig,
+    states: &HashMap<String, ServoState>,
+    planned: &HashMap<&str, f64>,
+) -> Option<String> {
+    // A servo in an unknown position could be anywhere
+    let leaves_free_range =
+        servo_span(&interlock.servo, states, planned).is_none_or(|(from, to)| {
+            !in_range(interlock.free_range, from) || !in_range(interlock.free_range, to)
+        });
+    if !leaves_free_range {
+        return None;
+    }
+
+    let required = servo_span(&interlock.requires_servo, states, planned);
+    if required.is_some_and(|(from, to)| {
+        in_range(interlock.requires_range, from) && in_range(interlock.requires_range, to)
+    }) {
+        return None;
+    }
+
+    let position = match required {
+        Some((from, to)) if from == to => format!("it is at {}", from),
+        Some((from, to)) => format!("it is moving from {} to {}", from, to),
+        None => "its position is unknown".to_string(),
+    };
+    Some(format!(
+        "Interlock '{}': '{}' may only leave [{}, {}] while '{}' is within [{}, {}], but {}",
+        interlock.name,
+        interlock.servo,
+        interlock.free_range[0],
+        interlock.free_range[1],
+        interlock.requires_servo,
+        interlock.requires_range[0],
+        interlock.requires_range[1],
+        position
+    ))
+}
+
+// Where the servo is and where it is headed, planned move first, as (from, to)
+fn servo_span(
+    servo: &str,
+    states: &HashMap<String, ServoState>,
+    planned: &HashMap<&str, f64>,
+) -> Option<(f64, f64)> {
+    let state = states.get(servo);
+    let current = state.and_then(|state| state.angle);
+    match planned.get(servo) {
+        // The first move after startup jumps straight to its target
+        Some(&to) => Some((current.unwrap_or(to), to)),
+        None => {
+            let current = current?;
+            let to = state
+                .filter(|state| state.moving)
+                .and_then(|state| state.target)
+                .unwrap_or(current);
+            Some((current, to))
+        }
+    }
+}
+
+fn in_range([min, max]: [f64; 2], angle: f64) -> bool {
+    (min..=max).contains(&angle)
+}
+
+// Waits for trajectories handed to the motion loop; the first failure wins
+async fn join_tracks(
+    finished: Vec<oneshot::Receiver<Result<(), HardwareError>>>,
+) -> Result<(), HardwareError> {
+    let mut result = Ok(());
+    for finished in finished {
+        let outcome = finished
+            .await
+            .unwrap_or_else(|_| Err(HardwareError::Other("Motion loop stopped".to_string())));
+        if result.is_ok() {
+            result = outcome;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::servo::lease::Owner;
+    use serde_json::json;
+
+    fn manager() -> ServoManager {
+        ServoManager::new(&TraceConfig::default())
+    }
+
+    fn servo(fields: serde_json::Value) -> ServoConfig {
+        let mut config = json!({
+            "name": "Pie Panel 1",
+            "controller_id": "dome",
+            "channel": 0,
+            "min_angle": 0,
+            "max_angle": 90,
+            "min_pulse": 200,
+            "max_pulse": 500
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        serde_json::from_value(config).unwrap()
+    }
+
+    // A simulated dome board with two pie panels on it
+    async fn rig() -> ServoManager {
+        let manager = manager();
+        let controller = serde_json::from_value(json!({
+            "id": "dome",
+            "i2c_address": "0x40",
+            "frequency": 50,
+            "driver": "simulated"
+        }))
+        .unwrap();
+        manager.add_controller(controller).await.unwrap();
+        manager.add_servo(servo(json!({}))).await.unwrap();
+        manager
+            .add_servo(servo(json!({ "name": "Pie Panel 2", "channel": 1 })))
+            .await
+            .unwrap();
+        manager
+    }
+
+    fn lease(
+        owner: &str,
+        servos: &[&str],
+        priority: i32,
+        on_conflict: ConflictPolicy,
+    ) -> LeaseRequest {
+        LeaseRequest {
+            owner: owner.to_string(),
+            servos: servos.iter().map(|servo| servo.to_string()).collect(),
+            priority,
+            on_conflict,
+            timeout_ms: None,
+        }
+    }
+
+    #[test]
+    fn angles_map_linearly_onto_the_pulse_range() {
+        let manager = manager();
+        let servo = servo(json!({}));
+
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 0.0), 200);
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 45.0), 350);
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 90.0), 500);
+        assert_eq!(manager.pulse_to_angle(&servo, (200, 500), 350), 45.0);
+    }
+
+    #[test]
+    fn inverted_servos_mirror_the_angle() {
+        let manager = manager();
+        let servo = servo(json!({ "inverted": true }));
+
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 0.0), 500);
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 30.0), 400);
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 90.0), 200);
+        assert_eq!(manager.pulse_to_angle(&servo, (200, 500), 400), 30.0);
+    }
+
+    #[test]
+    fn trim_shifts_the_pulse_but_not_the_angle() {
+        let manager = manager();
+        let servo = servo(json!({ "trim_degrees": 3.0 }));
+
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 30.0), 310);
+        assert_eq!(manager.pulse_to_angle(&servo, (200, 500), 310), 30.0);
+    }
+
+    #[test]
+    fn trim_applies_after_inversion() {
+        let manager = manager();
+        let servo = servo(json!({ "inverted": true, "trim_degrees": -3.0 }));
+
+        // 30° mirrors to 60°, then the trim pulls it back to 57°
+        assert_eq!(manager.angle_to_pulse(&servo, (200, 500), 30.0), 390);
+        assert_eq!(manager.pulse_to_angle(&servo, (200, 500), 390), 30.0);
+    }
+
+    #[test]
+    fn trim_never_pushes_past_the_end_stops() {
+        let manager = manager();
+        let up = servo(json!({ "trim_degrees": 5.0 }));
+        let down = servo(json!({ "trim_degrees": -5.0 }));
+
+        assert_eq!(manager.angle_to_pulse(&up, (200, 500), 90.0), 500);
+        assert_eq!(manager.angle_to_pulse(&down, (200, 500), 0.0), 200);
+    }
+
+    #[test]
+    fn moves_from_an_unknown_angle_allow_for_a_full_sweep() {
+        let config = servo(json!({ "max_speed_deg_per_s": 90 }));
+
+        let outcome = plan_move(&config, None, 90.0, 0, Easing::Linear);
+        assert_eq!(outcome.duration_ms, 1000);
+        assert!(outcome.clamped);
+
+        let outcome = plan_move(&config, Some(80.0), 90.0, 0, Easing::Linear);
+        assert_eq!(outcome.duration_ms, 112);
+    }
+
+    #[test]
+    fn speed_zero_holds_neutral() {
+        let config = servo(json!({ "kind": "continuous", "deadband": 10 }));
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, 0.0), 350);
+    }
+
+    #[test]
+    fn any_speed_clears_the_deadband() {
+        let config = servo(json!({ "kind": "continuous", "deadband": 10 }));
+
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, 0.01), 361);
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, -0.01), 339);
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, 1.0), 500);
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, -1.0), 200);
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, 0.5), 430);
+    }
+
+    #[test]
+    fn each_direction_scales_to_its_own_edge() {
+        let config = servo(json!({ "kind": "continuous" }));
+
+        // Neutral sits off-centre, so half speed is half of each side's span
+        assert_eq!(speed_to_pulse(&config, (200, 500), 300, 0.5), 400);
+        assert_eq!(speed_to_pulse(&config, (200, 500), 300, -0.5), 250);
+    }
+
+    #[test]
+    fn inverted_servos_turn_the_other_way() {
+        let config = servo(json!({ "kind": "continuous", "inverted": true }));
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, 1.0), 200);
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, -1.0), 500);
+    }
+
+    #[test]
+    fn a_deadband_wider_than_the_span_goes_to_the_edge() {
+        let config = servo(json!({ "kind": "continuous", "deadband": 500 }));
+        assert_eq!(speed_to_pulse(&config, (200, 500), 350, 0.1), 500);
+    }
+
+    #[tokio::test]
+    async fn leased_servos_reject_other_owners() {
+        let manager = rig().await;
+        manager
+            .acquire_lease(lease(
+                "dome-show",
+                &["Pie Panel 1"],
+                0,
+                ConflictPolicy::Reject,
+            ))
+            .await
+            .unwrap();
+
+        let other = lease(
+            "remote",
+            &["Pie Panel 1", "Pie Panel 2"],
+            5,
+            ConflictPolicy::Reject,
+        );
+        assert!(matches!(
+            manager.acquire_lease(other).await,
+            Err(HardwareError::Busy(_))
+        ));
+        // Nothing was leased by the failed request
+        assert!(!manager.list_leases().await.contains_key("Pie Panel 2"));
+
+        // The holder may take it again, and more besides
+        let again = lease(
+            "dome-show",
+            &["Pie Panel 1", "Pie Panel 2"],
+            0,
+            ConflictPolicy::Reject,
+        );
+        manager.acquire_lease(again).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_a_higher_priority_preempts() {
+        let manager = rig().await;
+        manager
+            .acquire_lease(lease(
+                "dome-show",
+                &["Pie Panel 1"],
+                5,
+                ConflictPolicy::Reject,
+            ))
+            .await
+            .unwrap();
+
+        let equal = lease("remote", &["Pie Panel 1"], 5, ConflictPolicy::Preempt);
+        assert!(manager.acquire_lease(equal).await.is_err());
+
+        let higher = lease("remote", &["Pie Panel 1"], 6, ConflictPolicy::Preempt);
+        manager.acquire_lease(higher).await.unwrap();
+        assert_eq!(manager.list_leases().await["Pie Panel 1"].owner, "remote");
+    }
+
+    #[tokio::test]
+    async fn queued_requests_wait_for_a_release() {
+        let manager = rig().await;
+        manager
+            .acquire_lease(lease(
+                "dome-show",
+                &["Pie Panel 1"],
+                0,
+                ConflictPolicy::Reject,
+            ))
+            .await
+            .unwrap();
+
+        let mut timed = lease("remote", &["Pie Panel 1"], 0, ConflictPolicy::Queue);
+        timed.timeout_ms = Some(20);
+        assert!(matches!(
+            manager.acquire_lease(timed).await,
+            Err(HardwareError::Timeout(_))
+        ));
+
+        let waiting = {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                let queued = lease("remote", &["Pie Panel 1"], 0, ConflictPolicy::Queue);
+                manager.acquire_lease(queued).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        manager.release_lease("dome-show").await.unwrap();
+        waiting.await.unwrap().unwrap();
+        assert_eq!(manager.list_leases().await["Pie Panel 1"].owner, "remote");
+    }
+
+    #[tokio::test]
+    async fn only_the_holder_may_drive_a_leased_servo() {
+        let manager = rig().await;
+        manager
+            .acquire_lease(lease(
+                "dome-show",
+                &["Pie Panel 1"],
+                0,
+                ConflictPolicy::Reject,
+            ))
+            .await
+            .unwrap();
+
+        for owner in [None, Some("remote")] {
+            let owner = owner.map(|owner| Arc::new(Owner::new(owner)));
+            let relax = run_as(owner.clone(), manager.relax_servo("Pie Panel 1")).await;
+            assert!(matches!(relax, Err(HardwareError::Busy(_))));
+            let pulse = run_as(owner, manager.set_calibration_pulse("Pie Panel 1", 300)).await;
+            assert!(matches!(pulse, Err(HardwareError::Busy(_))));
+        }
+
+        let holder = Some(Arc::new(Owner::new("dome-show")));
+        run_as(
+            holder,
+            manager.ensure_lease_holder(&["Pie Panel 1", "Pie Panel 2"]),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn an_emergency_stop_revokes_every_lease() {
+        let manager = rig().await;
+        manager
+            .acquire_lease(lease(
+                "dome-show",
+                &["Pie Panel 1"],
+                0,
+                ConflictPolicy::Reject,
+            ))
+            .await
+            .unwrap();
+
+        manager.engage_estop(EstopMode::Freeze).await.unwrap();
+        assert!(manager.list_leases().await.is_empty());
+    }
+
+    fn arm_interlock() -> Vec<InterlockConfig> {
+        vec![serde_json::from_value(json!({
+            "name": "Arm through door",
+            "servo": "Grabber Arm 1",
+            "free_range": [0, 10],
+            "requires_servo": "Utility Arm Door",
+            "requires_range": [80, 90]
+        }))
+        .unwrap()]
+    }
+
+    fn at(angle: f64) -> ServoState {
+        ServoState {
+            angle: Some(angle),
+            target: Some(angle),
+            ..ServoState::default()
+        }
+    }
+
+    fn moving(from: f64, to: f64) -> ServoState {
+        ServoState {
+            moving: true,
+            target: Some(to),
+            ..at(from)
+        }
+    }
+
+    fn states(entries: &[(&str, ServoState)]) -> HashMap<String, ServoState> {
+        entries
+            .iter()
+            .map(|(name, state)| (name.to_string(), state.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn servo_span_covers_where_a_servo_is_headed() {
+        let states = states(&[("Door", moving(0.0, 90.0)), ("Arm", at(5.0))]);
+        let none = HashMap::new();
+
+        assert_eq!(servo_span("Door", &states, &none), Some((0.0, 90.0)));
+        assert_eq!(servo_span("Arm", &states, &none), Some((5.0, 5.0)));
+        assert_eq!(servo_span("Unknown", &states, &none), None);
+
+        // A planned move replaces the running one, and jumps when the start is unknown
+        let planned = HashMap::from([("Door", 45.0), ("Unknown", 30.0)]);
+        assert_eq!(servo_span("Door", &states, &planned), Some((0.0, 45.0)));
+        assert_eq!(servo_span("Unknown", &states, &planned), Some((30.0, 30.0)));
+    }
+
+    #[test]
+    fn the_arm_may_swing_out_through_an_open_door() {
+        let interlocks = arm_interlock();
+        let states = states(&[("Grabber Arm 1", at(0.0)), ("Utility Arm Door", at(85.0))]);
+
+        let planned = HashMap::from([("Grabber Arm 1", 60.0)]);
+        assert!(check_interlocks(&interlocks, &states, &planned).is_ok());
+    }
+
+    #[test]
+    fn the_arm_stays_in_behind_a_closed_or_closing_door() {
+        let interlocks = arm_interlock();
+        let planned = HashMap::from([("Grabber Arm 1", 60.0)]);
+
+        let closed = states(&[("Grabber Arm 1", at(0.0)), ("Utility Arm Door", at(0.0))]);
+        assert!(check_interlocks(&interlocks, &closed, &planned).is_err());
+
+        let closing = states(&[
+            ("Grabber Arm 1", at(0.0)),
+            ("Utility Arm Door", moving(90.0, 0.0)),
+        ]);
+        assert!(check_interlocks(&interlocks, &closing, &planned).is_err());
+
+        let unknown = states(&[("Grabber Arm 1", at(0.0))]);
+        assert!(check_interlocks(&interlocks, &unknown, &planned).is_err());
+    }
+
+    #[test]
+    fn the_door_stays_open_while_the_arm_is_out() {
+        let interlocks = arm_interlock();
+        let states = states(&[("Grabber Arm 1", at(60.0)), ("Utility Arm Door", at(90.0))]);
+
+        let close = HashMap::from([("Utility Arm Door", 0.0)]);
+        assert!(check_interlocks(&interlocks, &states, &close).is_err());
+
+        // Pulling the arm in at the same time doesn't help, the door would close on it
+        let together = HashMap::from([("Utility Arm Door", 0.0), ("Grabber Arm 1", 0.0)]);
+        assert!(check_interlocks(&interlocks, &states, &together).is_err());
+    }
+
+    #[test]
+    fn unrelated_moves_ignore_the_interlock() {
+        let interlocks = arm_interlock();
+        // The arm is already out behind a closed door, e.g. moved by hand while relaxed
+        let states = states(&[("Grabber Arm 1", at(60.0)), ("Utility Arm Door", at(0.0))]);
+
+        let planned = HashMap::from([("Pie Panel 1", 85.0)]);
+        assert!(check_interlocks(&interlocks, &states, &planned).is_ok());
+    }
+
+    // The rig with its motion loop running
+    async fn running_rig() -> ServoManager {
+        let manager = rig().await;
+        tokio::spawn(manager.clone().run_motion(Duration::from_millis(5)));
+        manager
+    }
+
+    #[tokio::test]
+    async fn a_sweep_ends_on_its_target_pulse() {
+        let manager = running_rig().await;
+        manager
+            .move_servo_timed("Pie Panel 1", 90.0, 50, Easing::Linear)
+            .await
+            .unwrap();
+
+        let board = manager.simulated_board("dome").await.unwrap();
+        assert_eq!(board.channels()[0], Some(500));
+        let state = manager.get_servo("Pie Panel 1").await.unwrap().state;
+        assert_eq!(state.angle, Some(90.0));
+        assert_eq!(state.pulse, Some(500));
+    }
+
+    #[tokio::test]
+    async fn relaxing_mid_sweep_leaves_the_channel_off() {
+        let manager = running_rig().await;
+        manager.move_servo("Pie Panel 1", 0.0).await.unwrap();
+
+        let sweep = {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                manager
+                    .move_servo_timed("Pie Panel 1", 90.0, 200, Easing::Linear)
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        manager.relax_servo("Pie Panel 1").await.unwrap();
+        sweep.await.unwrap().unwrap();
+
+        // Nothing from the sweep lands after the relax
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let board = manager.simulated_board("dome").await.unwrap();
+        assert_eq!(board.channels()[0], None);
+        let last = board.writes().into_iter().rfind(|write| write.channel == 0);
+        assert_eq!(last.unwrap().pulse_width, None);
+        assert!(
+            manager
+                .get_servo("Pie Panel 1")
+                .await
+                .unwrap()
+                .state
+                .relaxed
+        );
+    }
+
+    #[tokio::test]
+    async fn speeds_go_out_through_the_motion_loop() {
+        let manager = running_rig().await;
+        manager
+            .add_servo(servo(json!({
+                "name": "Dome Motor",
+                "channel": 2,
+                "kind": "continuous"
+            })))
+            .await
+            .unwrap();
+
+        manager
+            .set_speed("Dome Motor", 0.5, Some(20))
+            .await
+            .unwrap();
+
+        let board = manager.simulated_board("dome").await.unwrap();
+        let pulses: Vec<Option<u16>> = board
+            .writes()
+            .into_iter()
+            .filter(|write| write.channel == 2)
+            .map(|write| write.pulse_width)
+            .collect();
+        assert_eq!(pulses.len(), 2);
+        assert_ne!(pulses[0], Some(350));
+        assert_eq!(pulses[1], Some(350));
+        let state = manager.get_servo("Dome Motor").await.unwrap().state;
+        assert_eq!(state.speed, Some(0.0));
+        assert!(!state.moving);
+    }
+
+    #[tokio::test]
+    async fn a_superseded_command_never_reaches_the_tracks() {
+        let manager = rig().await;
+        manager
+            .states
+            .lock()
+            .await
+            .entry("Pie Panel 1".to_string())
+            .or_default()
+            .generation = 2;
+
+        let config = manager.servo_config("Pie Panel 1").await.unwrap();
+        let controller = manager.controller("dome").await.unwrap();
+        let output = TrackOutput::Fixed {
+            servo: config.name.clone(),
+            generation: 1,
+            output: None,
+        };
+        let finished = manager
+            .add_tracks(vec![(
+                ("dome".to_string(), 0),
+                controller,
+                Sweep::hold(0.0),
+                output,
+            )])
+            .await;
+
+        assert!(join_tracks(finished).await.is_ok());
+        assert!(manager.tracks.lock().await.is_empty());
     }
 }
@@ -0,0 +1,65 @@
+use crate::errors::hardware_error::HardwareError;
+use crate::hardware::servo::actor::ControllerMetrics;
+use crate::hardware::servo::health::HealthStatus;
+use crate::hardware::servo::simulated::SimulatedBoard;
+use crate::hardware::servo::Pca9685Config;
+use async_trait::async_trait;
+
+// A board that drives PWM channels, shared by every manager that writes to it
+#[async_trait]
+pub trait PwmDriver: Send + Sync {
+    fn get_config(&self) -> &Pca9685Config;
+
+    // Frequency the driver really outputs, after prescale rounding
+    fn frequency(&self) -> f64;
+
+    // Error counters and the health they add up to
+    fn health(&self) -> HealthStatus;
+
+    // Queue depth, coalescing and write latency of the task that owns the board
+    fn metrics(&self) -> ControllerMetrics;
+
+    // What a simulated board has been sent; None for real hardware
+    fn simulated(&self) -> Option<SimulatedBoard> {
+        None
+    }
+
+    // Re-initializes a board that was reset or dropped off the bus, restoring its channels
+    async fn check_health(&self) -> Result<(), HardwareError>;
+
+    async fn set_pulse(&self, channel: u8, pulse_width: u16) -> Result<(), HardwareError>;
+
+    // Writes several (channel, pulse width) pairs so they all take effect together
+    async fn set_pulses(&self, pulses: &[(u8, u16)]) -> Result<(), HardwareError>;
+
+    // Stops driving the channel entirely; the next set_pulse brings it back
+    async fn set_full_off(&self, channel: u8) -> Result<(), HardwareError>;
+
+    // Holds the channel high for the whole cycle, for relays and switches
+    async fn set_full_on(&self, channel: u8) -> Result<(), HardwareError>;
+}
+
+// The board itself, real or simulated. Owned by a single controller task, which is the only
+// thing that ever talks to it
+#[async_trait]
+pub trait PwmBackend: Send {
+    fn get_config(&self) -> &Pca9685Config;
+
+    fn frequency(&self) -> f64;
+
+    fn health(&self) -> HealthStatus;
+
+    fn simulated(&self) -> Option<SimulatedBoard> {
+        None
+    }
+
+    async fn check_health(&mut self) -> Result<(), HardwareError>;
+
+    async fn set_pulse(&mut self, channel: u8, pulse_width: u16) -> Result<(), HardwareError>;
+
+    async fn set_pulses(&mut self, pulses: &[(u8, u16)]) -> Result<(), HardwareError>;
+
+    async fn set_full_off(&mut self, channel: u8) -> Result<(), HardwareError>;
+
+    async fn set_full_on(&mut self, channel: u8) -> Result<(), HardwareError>;
+}